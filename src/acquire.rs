@@ -0,0 +1,252 @@
+//! RAII guards for connections handed out by a [`TinSeaConn`](crate::TinSeaConn).
+
+use sea_orm::DatabaseConnection;
+use std::collections::BTreeMap;
+use std::ops::Deref;
+use std::panic::Location;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// How long [`AcquiredConn`] guards have been held, from
+/// [`TinSeaConn::hold_stats`](crate::TinSeaConn::hold_stats).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct HoldStats {
+    /// Guards currently held.
+    pub held: u32,
+    /// Guards dropped so far.
+    pub released: u64,
+    /// Total time the dropped guards were held.
+    pub total: Duration,
+    /// Longest time a dropped guard was held.
+    pub max: Duration,
+    /// Dropped guards that were held longer than the
+    /// [`leak_detection_threshold`](crate::DbConnector::leak_detection_threshold).
+    pub leaked: u64,
+}
+
+/// An [`AcquiredConn`] still held, from
+/// [`TinSeaConn::held`](crate::TinSeaConn::held).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct HeldConn {
+    /// Where [`acquire`](crate::TinSeaConn::acquire) was called.
+    pub location: &'static Location<'static>,
+    pub held_for: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Hold {
+    acquired_at: Instant,
+    location: &'static Location<'static>,
+}
+
+/// The guards of one [`TinSeaConn`](crate::TinSeaConn), shared by its
+/// clones.
+#[derive(Debug, Default)]
+pub(crate) struct Holds {
+    leak_threshold: Option<Duration>,
+    next_id: AtomicU64,
+    held: Mutex<BTreeMap<u64, Hold>>,
+    stats: Mutex<HoldStats>,
+    /// Set once the pool starts closing; no guards are handed out after.
+    closing: AtomicBool,
+    released: Notify,
+}
+
+impl Holds {
+    pub(crate) fn new(leak_threshold: Option<Duration>) -> Self {
+        Self {
+            leak_threshold,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn acquire(
+        self: &Arc<Self>,
+        conn: DatabaseConnection,
+        location: &'static Location<'static>,
+    ) -> AcquiredConn {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let hold = Hold {
+            acquired_at: Instant::now(),
+            location,
+        };
+        self.lock_held().insert(id, hold);
+        AcquiredConn {
+            conn,
+            holds: self.clone(),
+            id,
+            hold,
+        }
+    }
+
+    fn release(&self, id: u64, hold: Hold) {
+        let held_for = hold.acquired_at.elapsed();
+        self.lock_held().remove(&id);
+        let leaked = self.leak_threshold.is_some_and(|max| held_for > max);
+        {
+            let mut stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+            stats.released += 1;
+            stats.total += held_for;
+            stats.max = stats.max.max(held_for);
+            stats.leaked += leaked as u64;
+        }
+        if leaked {
+            log::warn!(
+                "Connection acquired at {} was held for {held_for:?}, longer than the leak detection threshold",
+                hold.location
+            );
+        }
+        self.released.notify_waiters();
+    }
+
+    fn lock_held(&self) -> std::sync::MutexGuard<'_, BTreeMap<u64, Hold>> {
+        self.held.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn stats(&self) -> HoldStats {
+        HoldStats {
+            held: self.lock_held().len() as u32,
+            ..*self.stats.lock().unwrap_or_else(|e| e.into_inner())
+        }
+    }
+
+    /// Guards still held, longest-held first.
+    pub(crate) fn held(&self) -> Vec<HeldConn> {
+        let mut held: Vec<_> = self
+            .lock_held()
+            .values()
+            .map(|hold| HeldConn {
+                location: hold.location,
+                held_for: hold.acquired_at.elapsed(),
+            })
+            .collect();
+        held.sort_by_key(|hold| std::cmp::Reverse(hold.held_for));
+        held
+    }
+
+    pub(crate) fn is_closing(&self) -> bool {
+        self.closing.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn start_closing(&self) {
+        self.closing.store(true, Ordering::Release);
+    }
+
+    /// Waits until every guard has been dropped.
+    pub(crate) async fn drained(&self) {
+        loop {
+            let released = self.released.notified();
+            if self.lock_held().is_empty() {
+                return;
+            }
+            released.await;
+        }
+    }
+}
+
+/// A connection checked out with [`TinSeaConn::acquire`](crate::TinSeaConn::acquire).
+///
+/// Derefs to the pool's [`DatabaseConnection`], so entities and raw
+/// statements run on it directly. Dropping it, including when the future
+/// holding it is cancelled, records how long it was held, logs a warning
+/// if that exceeds the
+/// [`leak_detection_threshold`](crate::DbConnector::leak_detection_threshold),
+/// and lets [`close_gracefully`](crate::TinSeaConn::close_gracefully) stop
+/// waiting for it.
+#[derive(Debug)]
+pub struct AcquiredConn {
+    conn: DatabaseConnection,
+    holds: Arc<Holds>,
+    id: u64,
+    hold: Hold,
+}
+
+impl AcquiredConn {
+    pub fn held_for(&self) -> Duration {
+        self.hold.acquired_at.elapsed()
+    }
+
+    /// Where [`acquire`](crate::TinSeaConn::acquire) was called.
+    pub fn location(&self) -> &'static Location<'static> {
+        self.hold.location
+    }
+}
+
+impl Deref for AcquiredConn {
+    type Target = DatabaseConnection;
+
+    fn deref(&self) -> &DatabaseConnection {
+        &self.conn
+    }
+}
+
+impl AsRef<DatabaseConnection> for AcquiredConn {
+    fn as_ref(&self) -> &DatabaseConnection {
+        &self.conn
+    }
+}
+
+impl Drop for AcquiredConn {
+    fn drop(&mut self) {
+        self.holds.release(self.id, self.hold);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Holds;
+    use sea_orm::DatabaseConnection;
+    use std::panic::Location;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn records_hold_durations() {
+        let holds = Arc::new(Holds::new(Some(Duration::from_millis(20))));
+        let first = holds.acquire(DatabaseConnection::Disconnected, Location::caller());
+        let second = holds.acquire(DatabaseConnection::Disconnected, Location::caller());
+        assert_eq!(holds.stats().held, 2);
+        assert_eq!(holds.held().len(), 2);
+
+        drop(first);
+        std::thread::sleep(Duration::from_millis(30));
+        drop(second);
+
+        let stats = holds.stats();
+        assert_eq!(stats.held, 0);
+        assert_eq!(stats.released, 2);
+        assert_eq!(stats.leaked, 1);
+        assert!(stats.max >= Duration::from_millis(30));
+        assert!(holds.held().is_empty());
+    }
+
+    #[test]
+    fn drained_waits_for_every_guard() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let holds = Arc::new(Holds::new(None));
+            holds.drained().await;
+
+            let guard = holds.acquire(DatabaseConnection::Disconnected, Location::caller());
+            let waiter = tokio::spawn({
+                let holds = holds.clone();
+                async move { holds.drained().await }
+            });
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            assert!(!waiter.is_finished());
+            drop(guard);
+            tokio::time::timeout(Duration::from_secs(1), waiter)
+                .await
+                .unwrap()
+                .unwrap();
+        });
+    }
+}
@@ -0,0 +1,113 @@
+//! App data and extractors for actix-web services, behind the `actix`
+//! feature.
+
+use crate::{ConnectionError, Db, DbConnector, DbRejection, TinSeaConn};
+use actix_web::dev::Payload;
+use actix_web::http::StatusCode;
+use actix_web::{FromRequest, HttpRequest, ResponseError, web};
+use sea_orm::DatabaseConnection;
+use std::future::{Ready, ready};
+
+impl DbConnector {
+    /// Connects a [`TinSeaConn`] to register with `App::app_data`, for the
+    /// [`Db`] extractor.
+    ///
+    /// ```ignore
+    /// let db = connector.into_actix_data().await?;
+    /// HttpServer::new({
+    ///     let db = db.clone();
+    ///     move || App::new().app_data(db.clone()).service(list_users)
+    /// })
+    /// .bind(("0.0.0.0", 8080))?
+    /// .run()
+    /// .await?;
+    /// db.get_ref()
+    ///     .clone()
+    ///     .close_gracefully(Instant::now() + Duration::from_secs(10))
+    ///     .await?;
+    /// ```
+    pub async fn into_actix_data(self) -> Result<web::Data<TinSeaConn>, ConnectionError> {
+        self.connect_managed().await.map(web::Data::new)
+    }
+}
+
+/// The pool registered with `App::app_data`: a [`TinSeaConn`], or a plain
+/// [`DatabaseConnection`], either bare or in `web::Data`. A plain
+/// connection's guards are not tracked by
+/// [`close_gracefully`](TinSeaConn::close_gracefully).
+fn registered(req: &HttpRequest) -> Option<TinSeaConn> {
+    if let Some(db) = req.app_data::<web::Data<TinSeaConn>>() {
+        return Some(db.get_ref().clone());
+    }
+    if let Some(db) = req.app_data::<TinSeaConn>() {
+        return Some(db.clone());
+    }
+    req.app_data::<web::Data<DatabaseConnection>>()
+        .map(|conn| conn.get_ref())
+        .or_else(|| req.app_data::<DatabaseConnection>())
+        .map(|conn| TinSeaConn::new(conn.clone()))
+}
+
+/// Acquires the connection from the pool registered with `App::app_data`.
+/// Without one, the request fails with `500 Internal Server Error`.
+impl FromRequest for Db {
+    type Error = DbRejection;
+    type Future = Ready<Result<Self, DbRejection>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(match registered(req) {
+            Some(db) => db.acquire().map(Db).map_err(DbRejection),
+            None => Err(DbRejection(ConnectionError::InvalidConfig(
+                "No TinSeaConn or DatabaseConnection is registered with App::app_data".into(),
+            ))),
+        })
+    }
+}
+
+impl ResponseError for DbRejection {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(DbRejection::status_code(self))
+            .unwrap_or(StatusCode::SERVICE_UNAVAILABLE)
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use crate::{Db, TinSeaConn};
+    use actix_web::test::TestRequest;
+    use actix_web::{FromRequest, ResponseError, web};
+
+    #[test]
+    fn extracts_from_app_data() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let conn = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+            let db = TinSeaConn::new(conn.clone());
+
+            let req = TestRequest::default()
+                .app_data(web::Data::new(db.clone()))
+                .to_http_request();
+            let Db(guard) = Db::extract(&req).await.unwrap();
+            assert_eq!(db.hold_stats().held, 1);
+            drop(guard);
+
+            let req = TestRequest::default().app_data(conn).to_http_request();
+            assert!(Db::extract(&req).await.is_ok());
+
+            let missing = Db::extract(&TestRequest::default().to_http_request())
+                .await
+                .unwrap_err();
+            assert_eq!(ResponseError::status_code(&missing), 500);
+
+            let req = TestRequest::default()
+                .app_data(db.clone())
+                .to_http_request();
+            db.clone().close().await.unwrap();
+            let closed = Db::extract(&req).await.unwrap_err();
+            assert_eq!(ResponseError::status_code(&closed), 503);
+        });
+    }
+}
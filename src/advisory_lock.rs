@@ -0,0 +1,120 @@
+use crate::ConnectionError;
+use sea_orm::sqlx::pool::PoolConnection;
+use sea_orm::sqlx::{self, PgConnection, Postgres};
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend};
+
+/// A Postgres session-level advisory lock (`pg_advisory_lock`), held on a
+/// connection taken out of the pool for as long as the lock lives.
+///
+/// Useful for leader election or keeping two instances from running
+/// migrations at once. [`release`](Self::release) unlocks and hands the
+/// connection back. Dropping the guard instead closes that connection,
+/// which makes the server release the lock; either way the lock never
+/// stays behind on a pooled connection. The lock is also lost if the
+/// connection breaks, so long-lived holders should check
+/// [`is_held`](Self::is_held) now and then.
+#[derive(Debug)]
+pub struct AdvisoryLock {
+    conn: Option<PoolConnection<Postgres>>,
+    key: i64,
+}
+
+impl AdvisoryLock {
+    /// Waits until the lock on `key` is free and takes it.
+    pub async fn acquire(conn: &DatabaseConnection, key: i64) -> Result<Self, ConnectionError> {
+        let mut lock = Self::checkout(conn, key).await?;
+        sqlx::query("SELECT pg_advisory_lock($1)")
+            .bind(key)
+            .execute(lock.connection())
+            .await
+            .map_err(|e| lock_error(key, e))?;
+        Ok(lock)
+    }
+
+    /// Takes the lock on `key` if nobody holds it, without waiting.
+    pub async fn try_acquire(
+        conn: &DatabaseConnection,
+        key: i64,
+    ) -> Result<Option<Self>, ConnectionError> {
+        let mut lock = Self::checkout(conn, key).await?;
+        let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock($1)")
+            .bind(key)
+            .fetch_one(lock.connection())
+            .await
+            .map_err(|e| lock_error(key, e))?;
+        if !locked {
+            // Nothing is held, so the connection can go straight back.
+            lock.conn.take();
+            return Ok(None);
+        }
+        Ok(Some(lock))
+    }
+
+    async fn checkout(conn: &DatabaseConnection, key: i64) -> Result<Self, ConnectionError> {
+        if conn.get_database_backend() != DbBackend::Postgres {
+            return Err(ConnectionError::InvalidConfig(
+                "Advisory locks require a PostgreSQL connection".into(),
+            ));
+        }
+        let conn = conn
+            .get_postgres_connection_pool()
+            .acquire()
+            .await
+            .map_err(|e| ConnectionError::ConnectionFailed(e.to_string()))?;
+        Ok(Self {
+            conn: Some(conn),
+            key,
+        })
+    }
+
+    fn connection(&mut self) -> &mut PgConnection {
+        self.conn.as_mut().expect("advisory lock connection taken")
+    }
+
+    pub fn key(&self) -> i64 {
+        self.key
+    }
+
+    /// Whether the server still has the lock on record for this
+    /// connection.
+    pub async fn is_held(&mut self) -> Result<bool, ConnectionError> {
+        sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM pg_locks WHERE locktype = 'advisory' \
+             AND objsubid = 1 AND pid = pg_backend_pid() AND granted \
+             AND ((classid::bigint << 32) | objid::bigint) = $1)",
+        )
+        .bind(self.key)
+        .fetch_one(self.connection())
+        .await
+        .map_err(|e| ConnectionError::DatabaseError(e.to_string()))
+    }
+
+    /// Unlocks and returns the connection to the pool.
+    pub async fn release(mut self) -> Result<(), ConnectionError> {
+        let key = self.key;
+        let unlocked: bool = sqlx::query_scalar("SELECT pg_advisory_unlock($1)")
+            .bind(key)
+            .fetch_one(self.connection())
+            .await
+            .map_err(|e| ConnectionError::DatabaseError(format!("Unlocking {key} failed: {e}")))?;
+        if !unlocked {
+            return Err(ConnectionError::DatabaseError(format!(
+                "Advisory lock {key} was no longer held"
+            )));
+        }
+        self.conn.take();
+        Ok(())
+    }
+}
+
+impl Drop for AdvisoryLock {
+    fn drop(&mut self) {
+        if let Some(mut conn) = self.conn.take() {
+            conn.close_on_drop();
+        }
+    }
+}
+
+fn lock_error(key: i64, e: sqlx::Error) -> ConnectionError {
+    ConnectionError::DatabaseError(format!("Locking {key} failed: {e}"))
+}
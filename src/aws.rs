@@ -0,0 +1,256 @@
+//! AWS credential loading and SigV4 signing for RDS IAM authentication and
+//! Secrets Manager.
+
+use crate::ConnectionError;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// RDS auth tokens are valid for 15 minutes; refresh well before that.
+#[cfg(feature = "aws-iam")]
+pub(crate) const TOKEN_REFRESH_INTERVAL: std::time::Duration =
+    std::time::Duration::from_secs(10 * 60);
+
+#[derive(Clone)]
+pub(crate) struct AwsCredentials {
+    pub(crate) access_key_id: String,
+    pub(crate) secret_access_key: String,
+    pub(crate) session_token: Option<String>,
+}
+
+/// Region and credentials profile used to sign RDS auth tokens.
+#[cfg(feature = "aws-iam")]
+#[derive(Debug, Clone)]
+pub(crate) struct AwsIamAuth {
+    pub(crate) region: String,
+    pub(crate) profile: String,
+}
+
+#[cfg(feature = "aws-iam")]
+impl AwsIamAuth {
+    /// Generates a fresh auth token for `username` on `host:port`.
+    pub(crate) fn token(
+        &self,
+        host: &str,
+        port: u16,
+        username: &str,
+    ) -> Result<String, ConnectionError> {
+        let credentials = load_credentials(&self.profile)?;
+        Ok(rds_auth_token(
+            host,
+            port,
+            username,
+            &self.region,
+            &credentials,
+            SystemTime::now(),
+        ))
+    }
+}
+
+/// Loads credentials from `AWS_ACCESS_KEY_ID` / `AWS_SECRET_ACCESS_KEY` /
+/// `AWS_SESSION_TOKEN`, falling back to `profile` in the shared credentials
+/// file (`$AWS_SHARED_CREDENTIALS_FILE` or `~/.aws/credentials`).
+pub(crate) fn load_credentials(profile: &str) -> Result<AwsCredentials, ConnectionError> {
+    if let (Ok(access_key_id), Ok(secret_access_key)) = (
+        std::env::var("AWS_ACCESS_KEY_ID"),
+        std::env::var("AWS_SECRET_ACCESS_KEY"),
+    ) {
+        return Ok(AwsCredentials {
+            access_key_id,
+            secret_access_key,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        });
+    }
+
+    let path = std::env::var_os("AWS_SHARED_CREDENTIALS_FILE")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".aws/credentials"))
+        })
+        .ok_or_else(|| {
+            ConnectionError::InvalidConfig("No AWS credentials found in environment".into())
+        })?;
+    let content = std::fs::read_to_string(&path).map_err(|e| {
+        ConnectionError::InvalidConfig(format!(
+            "Failed to read AWS credentials file {}: {e}",
+            path.display()
+        ))
+    })?;
+
+    let mut in_profile = false;
+    let (mut access_key_id, mut secret_access_key, mut session_token) = (None, None, None);
+    for line in content.lines() {
+        let line = line.trim();
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_profile = section.trim() == profile;
+            continue;
+        }
+        if !in_profile {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            let value = value.trim().to_string();
+            match key.trim() {
+                "aws_access_key_id" => access_key_id = Some(value),
+                "aws_secret_access_key" => secret_access_key = Some(value),
+                "aws_session_token" => session_token = Some(value),
+                _ => {}
+            }
+        }
+    }
+
+    match (access_key_id, secret_access_key) {
+        (Some(access_key_id), Some(secret_access_key)) => Ok(AwsCredentials {
+            access_key_id,
+            secret_access_key,
+            session_token,
+        }),
+        _ => Err(ConnectionError::InvalidConfig(format!(
+            "AWS profile '{profile}' not found in {}",
+            path.display()
+        ))),
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn sha256_hex(data: &str) -> String {
+    hex::encode(Sha256::digest(data.as_bytes()))
+}
+
+/// Percent-encodes per the SigV4 rules (RFC 3986 unreserved set only).
+#[cfg(feature = "aws-iam")]
+pub(crate) fn uri_encode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    out
+}
+
+/// Formats `time` as the SigV4 `(YYYYMMDD, YYYYMMDDTHHMMSSZ)` pair.
+pub(crate) fn amz_dates(time: SystemTime) -> (String, String) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Civil-from-days, proleptic Gregorian calendar.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    let date = format!("{year:04}{month:02}{day:02}");
+    let datetime = format!(
+        "{date}T{:02}{:02}{:02}Z",
+        rem / 3_600,
+        (rem % 3_600) / 60,
+        rem % 60
+    );
+    (date, datetime)
+}
+
+/// Derives the SigV4 signing key and signs `string_to_sign`.
+pub(crate) fn sign(
+    credentials: &AwsCredentials,
+    date: &str,
+    region: &str,
+    service: &str,
+    string_to_sign: &str,
+) -> String {
+    let k_date = hmac_sha256(
+        format!("AWS4{}", credentials.secret_access_key).as_bytes(),
+        date,
+    );
+    let k_region = hmac_sha256(&k_date, region);
+    let k_service = hmac_sha256(&k_region, service);
+    let k_signing = hmac_sha256(&k_service, "aws4_request");
+    hex::encode(hmac_sha256(&k_signing, string_to_sign))
+}
+
+/// Builds the string-to-sign for a canonical request.
+pub(crate) fn string_to_sign(
+    datetime: &str,
+    date: &str,
+    region: &str,
+    service: &str,
+    canonical_request: &str,
+) -> String {
+    format!(
+        "AWS4-HMAC-SHA256\n{datetime}\n{date}/{region}/{service}/aws4_request\n{}",
+        sha256_hex(canonical_request)
+    )
+}
+
+pub(crate) fn payload_hash(payload: &str) -> String {
+    sha256_hex(payload)
+}
+
+/// Generates an RDS IAM auth token: a SigV4 presigned `connect` request for
+/// the `rds-db` service, without the `https://` prefix.
+#[cfg(feature = "aws-iam")]
+pub(crate) fn rds_auth_token(
+    host: &str,
+    port: u16,
+    username: &str,
+    region: &str,
+    credentials: &AwsCredentials,
+    now: SystemTime,
+) -> String {
+    let service = "rds-db";
+    let (date, datetime) = amz_dates(now);
+    let scope = format!("{date}/{region}/{service}/aws4_request");
+    let endpoint = format!("{host}:{port}");
+
+    let mut params = vec![
+        ("Action".to_string(), "connect".to_string()),
+        ("DBUser".to_string(), username.to_string()),
+        (
+            "X-Amz-Algorithm".to_string(),
+            "AWS4-HMAC-SHA256".to_string(),
+        ),
+        (
+            "X-Amz-Credential".to_string(),
+            format!("{}/{scope}", credentials.access_key_id),
+        ),
+        ("X-Amz-Date".to_string(), datetime.clone()),
+        ("X-Amz-Expires".to_string(), "900".to_string()),
+        ("X-Amz-SignedHeaders".to_string(), "host".to_string()),
+    ];
+    if let Some(token) = &credentials.session_token {
+        params.push(("X-Amz-Security-Token".to_string(), token.clone()));
+    }
+    params.sort();
+    let query = params
+        .iter()
+        .map(|(k, v)| format!("{}={}", uri_encode(k), uri_encode(v)))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let canonical_request = format!(
+        "GET\n/\n{query}\nhost:{endpoint}\n\nhost\n{}",
+        payload_hash("")
+    );
+    let to_sign = string_to_sign(&datetime, &date, region, service, &canonical_request);
+    let signature = sign(credentials, &date, region, service, &to_sign);
+
+    format!("{endpoint}/?{query}&X-Amz-Signature={signature}")
+}
@@ -0,0 +1,189 @@
+//! Database credentials from AWS Secrets Manager, in the JSON layout RDS
+//! uses for its managed secrets.
+
+use crate::ConnectionError;
+use crate::aws;
+use sea_orm::DatabaseConnection;
+use serde::Deserialize;
+use std::time::{Duration, SystemTime};
+
+const SERVICE: &str = "secretsmanager";
+
+/// A secret to read, by ARN or name.
+#[derive(Debug, Clone)]
+pub(crate) struct AwsSecret {
+    pub(crate) secret_id: String,
+    pub(crate) refresh_interval: Option<Duration>,
+}
+
+/// The fields of an RDS database secret.
+#[derive(Deserialize)]
+pub(crate) struct RdsSecret {
+    pub(crate) username: String,
+    pub(crate) password: String,
+    pub(crate) host: Option<String>,
+    #[serde(default, deserialize_with = "port")]
+    pub(crate) port: Option<u16>,
+    pub(crate) dbname: Option<String>,
+}
+
+/// RDS writes the port as a number, but hand-written secrets often use a
+/// string.
+fn port<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<u16>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Port {
+        Number(u16),
+        Text(String),
+    }
+    match Option::<Port>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Port::Number(port)) => Ok(Some(port)),
+        Some(Port::Text(port)) => port.parse().map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+#[derive(Deserialize)]
+struct GetSecretValueResponse {
+    #[serde(rename = "SecretString")]
+    secret_string: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    #[serde(rename = "__type")]
+    kind: Option<String>,
+    #[serde(alias = "Message")]
+    message: Option<String>,
+}
+
+impl AwsSecret {
+    /// The region is taken from the ARN, or from `AWS_REGION` /
+    /// `AWS_DEFAULT_REGION` when the secret is given by name.
+    fn region(&self) -> Result<String, ConnectionError> {
+        if let Some(region) = self
+            .secret_id
+            .strip_prefix("arn:")
+            .and_then(|arn| arn.split(':').nth(2))
+            .filter(|region| !region.is_empty())
+        {
+            return Ok(region.to_string());
+        }
+        std::env::var("AWS_REGION")
+            .or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+            .map_err(|_| {
+                ConnectionError::InvalidConfig(format!(
+                    "Cannot determine the region for secret '{}'; use an ARN or set AWS_REGION",
+                    self.secret_id
+                ))
+            })
+    }
+
+    /// Reads and parses the current version of the secret.
+    pub(crate) async fn fetch(&self) -> Result<RdsSecret, ConnectionError> {
+        let region = self.region()?;
+        let profile = std::env::var("AWS_PROFILE").unwrap_or_else(|_| "default".into());
+        let credentials = aws::load_credentials(&profile)?;
+
+        let host = format!("{SERVICE}.{region}.amazonaws.com");
+        let target = "secretsmanager.GetSecretValue";
+        let content_type = "application/x-amz-json-1.1";
+        let body = serde_json::json!({ "SecretId": self.secret_id }).to_string();
+        let (date, datetime) = aws::amz_dates(SystemTime::now());
+
+        let mut headers = vec![
+            ("content-type", content_type),
+            ("host", host.as_str()),
+            ("x-amz-date", datetime.as_str()),
+            ("x-amz-target", target),
+        ];
+        if let Some(token) = &credentials.session_token {
+            headers.push(("x-amz-security-token", token));
+        }
+        headers.sort();
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{name}:{value}\n"))
+            .collect();
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_request = format!(
+            "POST\n/\n\n{canonical_headers}\n{signed_headers}\n{}",
+            aws::payload_hash(&body)
+        );
+        let to_sign = aws::string_to_sign(&datetime, &date, &region, SERVICE, &canonical_request);
+        let signature = aws::sign(&credentials, &date, &region, SERVICE, &to_sign);
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{date}/{region}/{SERVICE}/aws4_request, SignedHeaders={signed_headers}, Signature={signature}",
+            credentials.access_key_id
+        );
+        headers.retain(|(name, _)| *name != "host");
+        headers.push(("authorization", &authorization));
+
+        let url = format!("https://{host}/");
+        let (status, response) =
+            crate::http::request("POST", &url, &headers, Some(&body), None).await?;
+        if status != 200 {
+            let detail = serde_json::from_str::<ErrorResponse>(&response)
+                .map(|e| {
+                    format!(
+                        "{}: {}",
+                        e.kind.unwrap_or_default(),
+                        e.message.unwrap_or_default()
+                    )
+                })
+                .unwrap_or_default();
+            return Err(ConnectionError::ConnectionFailed(format!(
+                "Secrets Manager returned HTTP {status} for '{}': {detail}",
+                self.secret_id
+            )));
+        }
+
+        let invalid = |e: serde_json::Error| {
+            ConnectionError::InvalidConfig(format!("Invalid secret '{}': {e}", self.secret_id))
+        };
+        let secret_string = serde_json::from_str::<GetSecretValueResponse>(&response)
+            .map_err(invalid)?
+            .secret_string
+            .ok_or_else(|| {
+                ConnectionError::InvalidConfig(format!(
+                    "Secret '{}' has no SecretString",
+                    self.secret_id
+                ))
+            })?;
+        serde_json::from_str(&secret_string).map_err(invalid)
+    }
+}
+
+/// Re-reads the secret every `interval` and installs the credentials in the
+/// pool when they change, so a rotation is picked up by new connections
+/// before the old password stops working.
+pub(crate) fn spawn_refresh(conn: &DatabaseConnection, secret: AwsSecret, interval: Duration) {
+    let conn = conn.clone();
+    crate::tasks::spawn("tin-sea-conn::aws-secret-refresh", async move {
+        let mut current = None;
+        loop {
+            tokio::time::sleep(interval).await;
+            if crate::managed::is_closed(&conn) {
+                break;
+            }
+            match secret.fetch().await {
+                Ok(fetched) => {
+                    let credentials = (fetched.username, fetched.password);
+                    if current.as_ref() != Some(&credentials) {
+                        crate::refresh::set_credentials(
+                            &conn,
+                            Some(&credentials.0),
+                            &credentials.1,
+                        );
+                        current = Some(credentials);
+                    }
+                }
+                Err(e) => log::warn!("Failed to refresh secret '{}': {e}", secret.secret_id),
+            }
+        }
+    });
+}
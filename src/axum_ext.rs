@@ -0,0 +1,100 @@
+//! State and extractors for axum services, behind the `axum` feature.
+
+use crate::{ConnectionError, Db, DbConnector, DbRejection, TinSeaConn};
+use axum::extract::{FromRef, FromRequestParts};
+use axum::http::StatusCode;
+use axum::http::request::Parts;
+use axum::response::{IntoResponse, Response};
+use sea_orm::DatabaseConnection;
+
+impl DbConnector {
+    /// Connects a [`TinSeaConn`] to serve as the router's state, or as a
+    /// field of it with [`FromRef`], for the [`Db`] extractor.
+    ///
+    /// ```ignore
+    /// let db = connector.into_axum_state().await?;
+    /// let app = Router::new()
+    ///     .route("/users", get(list_users))
+    ///     .route("/ready", get(health::handler(db.clone())))
+    ///     .with_state(db.clone());
+    /// axum::serve(listener, app)
+    ///     .with_graceful_shutdown(db.shutdown_on(shutdown_signal()))
+    ///     .await?;
+    /// db.close_gracefully(Instant::now() + Duration::from_secs(10)).await?;
+    /// ```
+    pub async fn into_axum_state(self) -> Result<TinSeaConn, ConnectionError> {
+        self.connect_managed().await
+    }
+}
+
+/// Lets a plain [`DatabaseConnection`] state serve the [`Db`] extractor.
+/// Its connections are not tracked by
+/// [`close_gracefully`](TinSeaConn::close_gracefully); prefer a
+/// [`TinSeaConn`] from [`DbConnector::into_axum_state`].
+impl FromRef<DatabaseConnection> for TinSeaConn {
+    fn from_ref(conn: &DatabaseConnection) -> Self {
+        TinSeaConn::new(conn.clone())
+    }
+}
+
+/// Acquires the connection from the router's [`TinSeaConn`] state.
+impl<S> FromRequestParts<S> for Db
+where
+    TinSeaConn: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = DbRejection;
+
+    async fn from_request_parts(_parts: &mut Parts, state: &S) -> Result<Self, DbRejection> {
+        TinSeaConn::from_ref(state)
+            .acquire()
+            .map(Db)
+            .map_err(DbRejection)
+    }
+}
+
+impl IntoResponse for DbRejection {
+    fn into_response(self) -> Response {
+        let status =
+            StatusCode::from_u16(self.status_code()).unwrap_or(StatusCode::SERVICE_UNAVAILABLE);
+        (status, self.0.to_string()).into_response()
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use crate::{Db, DbRejection, TinSeaConn};
+    use axum::extract::FromRequestParts;
+    use axum::http::{Request, StatusCode};
+    use axum::response::IntoResponse;
+    use std::time::Duration;
+
+    #[test]
+    fn extracts_until_the_pool_shuts_down() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let db = TinSeaConn::new(sea_orm::Database::connect("sqlite::memory:").await.unwrap());
+            let (mut parts, ()) = Request::new(()).into_parts();
+
+            let Db(conn) = Db::from_request_parts(&mut parts, &db).await.unwrap();
+            assert_eq!(db.hold_stats().held, 1);
+            drop(conn);
+
+            let (signal, received) = tokio::sync::oneshot::channel::<()>();
+            let shutdown = tokio::spawn(db.shutdown_on(async move {
+                let _ = received.await;
+            }));
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            assert!(Db::from_request_parts(&mut parts, &db).await.is_ok());
+            signal.send(()).unwrap();
+            shutdown.await.unwrap();
+
+            let DbRejection(e) = Db::from_request_parts(&mut parts, &db).await.unwrap_err();
+            let response = DbRejection(e).into_response();
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        });
+    }
+}
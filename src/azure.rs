@@ -0,0 +1,69 @@
+//! Microsoft Entra ID (Azure AD) authentication for Azure Database for
+//! PostgreSQL and MySQL.
+
+use crate::ConnectionError;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Entra tokens are valid for at least an hour; refresh well before that.
+pub(crate) const TOKEN_REFRESH_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+const RESOURCE: &str = "https%3A%2F%2Fossrdbms-aad.database.windows.net";
+const IMDS_HOST: &str = "169.254.169.254";
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+/// Fetches an access token for Azure Database from the managed identity
+/// endpoint: the App Service / Functions endpoint (`IDENTITY_ENDPOINT` and
+/// `IDENTITY_HEADER`) when present, otherwise the VM instance metadata
+/// service. `AZURE_CLIENT_ID` selects a user-assigned identity.
+pub(crate) async fn access_token() -> Result<String, ConnectionError> {
+    let client_id = std::env::var("AZURE_CLIENT_ID")
+        .ok()
+        .map(|id| format!("&client_id={id}"))
+        .unwrap_or_default();
+
+    let (status, body) = match (
+        std::env::var("IDENTITY_ENDPOINT"),
+        std::env::var("IDENTITY_HEADER"),
+    ) {
+        (Ok(endpoint), Ok(header)) => {
+            let rest = endpoint.strip_prefix("http://").ok_or_else(|| {
+                ConnectionError::InvalidConfig(format!("Unsupported IDENTITY_ENDPOINT: {endpoint}"))
+            })?;
+            let (hostport, path) = rest.split_once('/').unwrap_or((rest, ""));
+            let (host, port) = match hostport.rsplit_once(':') {
+                Some((host, port)) => (
+                    host,
+                    port.parse().map_err(|_| {
+                        ConnectionError::InvalidConfig(format!(
+                            "Invalid port in IDENTITY_ENDPOINT: {endpoint}"
+                        ))
+                    })?,
+                ),
+                None => (hostport, 80),
+            };
+            let path = format!("/{path}?api-version=2019-08-01&resource={RESOURCE}{client_id}");
+            crate::http::get(host, port, &path, &[("X-IDENTITY-HEADER", &header)]).await?
+        }
+        _ => {
+            let path = format!(
+                "/metadata/identity/oauth2/token?api-version=2018-02-01&resource={RESOURCE}{client_id}"
+            );
+            crate::http::get(IMDS_HOST, 80, &path, &[("Metadata", "true")]).await?
+        }
+    };
+
+    if status != 200 {
+        return Err(ConnectionError::ConnectionFailed(format!(
+            "Azure managed identity endpoint returned HTTP {status}"
+        )));
+    }
+    let response: TokenResponse = serde_json::from_str(&body).map_err(|e| {
+        ConnectionError::ConnectionFailed(format!("Invalid Azure token response: {e}"))
+    })?;
+    Ok(response.access_token)
+}
@@ -0,0 +1,163 @@
+use crate::PoolStats;
+use sea_orm::DatabaseConnection;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Chooses which replica serves the next read of a
+/// [`DbCluster`](crate::DbCluster).
+///
+/// `replicas` is never empty and is in the order the replicas were passed
+/// to [`DbCluster::connect`](crate::DbCluster::connect), so a custom policy
+/// (e.g. preferring replicas in the caller's zone) can key off the index.
+/// With [`DbCluster::discover`](crate::DbCluster::discover) it is in host
+/// order, skipping the primary and unreachable hosts. An out-of-range index
+/// wraps around.
+pub trait LoadBalancer: Send + Sync + 'static {
+    fn pick(&self, replicas: &[DatabaseConnection]) -> usize;
+}
+
+/// Takes the replicas in turn. The default.
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    next: AtomicUsize,
+}
+
+impl LoadBalancer for RoundRobin {
+    fn pick(&self, replicas: &[DatabaseConnection]) -> usize {
+        self.next.fetch_add(1, Ordering::Relaxed) % replicas.len()
+    }
+}
+
+/// Picks a replica uniformly at random.
+#[derive(Debug, Default)]
+pub struct Random {
+    state: RandomState,
+    counter: AtomicUsize,
+}
+
+impl LoadBalancer for Random {
+    fn pick(&self, replicas: &[DatabaseConnection]) -> usize {
+        let n = self.counter.fetch_add(1, Ordering::Relaxed);
+        (self.state.hash_one(n) % replicas.len() as u64) as usize
+    }
+}
+
+/// Picks the replica with the fewest connections checked out, rotating
+/// between replicas that tie.
+#[derive(Debug, Default)]
+pub struct LeastOutstanding {
+    next: AtomicUsize,
+}
+
+impl LoadBalancer for LeastOutstanding {
+    fn pick(&self, replicas: &[DatabaseConnection]) -> usize {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        (0..replicas.len())
+            .map(|offset| (start + offset) % replicas.len())
+            .min_by_key(|&idx| PoolStats::of(&replicas[idx]).in_use())
+            .unwrap_or(0)
+    }
+}
+
+/// Spreads reads in proportion to per-replica weights, interleaving them
+/// rather than sending runs to the heaviest replica.
+///
+/// Weights are given in replica order; replicas without one weigh 1, and a
+/// replica weighing 0 gets no reads unless every replica does.
+#[derive(Debug)]
+pub struct Weighted {
+    weights: Vec<u32>,
+    current: Mutex<Vec<i64>>,
+}
+
+impl Weighted {
+    pub fn new(weights: impl Into<Vec<u32>>) -> Self {
+        Self {
+            weights: weights.into(),
+            current: Mutex::default(),
+        }
+    }
+}
+
+impl LoadBalancer for Weighted {
+    /// Smooth weighted round-robin, as in nginx.
+    fn pick(&self, replicas: &[DatabaseConnection]) -> usize {
+        let mut weights: Vec<i64> = (0..replicas.len())
+            .map(|idx| self.weights.get(idx).copied().unwrap_or(1).into())
+            .collect();
+        if weights.iter().all(|&w| w == 0) {
+            weights.fill(1);
+        }
+        let total: i64 = weights.iter().sum();
+
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        current.resize(replicas.len(), 0);
+        for (value, weight) in current.iter_mut().zip(&weights) {
+            *value += weight;
+        }
+        let best = (0..replicas.len())
+            .max_by_key(|&idx| (current[idx], std::cmp::Reverse(idx)))
+            .unwrap_or(0);
+        current[best] -= total;
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LoadBalancer, Weighted};
+    use sea_orm::DatabaseConnection;
+
+    fn picks(balancer: &Weighted, replicas: usize, n: usize) -> Vec<usize> {
+        let replicas = vec![DatabaseConnection::Disconnected; replicas];
+        (0..n).map(|_| balancer.pick(&replicas)).collect()
+    }
+
+    fn counts(picks: &[usize], replicas: usize) -> Vec<usize> {
+        (0..replicas)
+            .map(|idx| picks.iter().filter(|&&pick| pick == idx).count())
+            .collect()
+    }
+
+    #[test]
+    fn weighted_interleaves_in_proportion() {
+        let balancer = Weighted::new([3, 1]);
+        assert_eq!(picks(&balancer, 2, 8), [0, 0, 1, 0, 0, 0, 1, 0]);
+
+        // nginx's example: a, a, b, a, c, a, a.
+        let balancer = Weighted::new([5, 1, 1]);
+        assert_eq!(picks(&balancer, 3, 7), [0, 0, 1, 0, 2, 0, 0]);
+
+        let balancer = Weighted::new([7, 2, 1]);
+        assert_eq!(counts(&picks(&balancer, 3, 1000), 3), [700, 200, 100]);
+    }
+
+    #[test]
+    fn weighted_defaults_missing_weights_to_one() {
+        let balancer = Weighted::new([2]);
+        assert_eq!(counts(&picks(&balancer, 3, 40), 3), [20, 10, 10]);
+        // Weights beyond the replicas are ignored.
+        let balancer = Weighted::new([1, 1, 8]);
+        assert_eq!(picks(&balancer, 2, 4), [0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn weighted_skips_zero_weights() {
+        let balancer = Weighted::new([0, 2, 0]);
+        assert_eq!(picks(&balancer, 3, 5), [1; 5]);
+
+        // Unless every replica weighs 0, when they share evenly.
+        let balancer = Weighted::new([0, 0]);
+        assert_eq!(picks(&balancer, 2, 4), [0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn weighted_with_a_single_replica() {
+        for weights in [vec![], vec![0], vec![5]] {
+            let balancer = Weighted::new(weights);
+            assert_eq!(picks(&balancer, 1, 3), [0; 3]);
+        }
+    }
+}
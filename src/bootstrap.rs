@@ -0,0 +1,184 @@
+//! Creating, and for tests resetting, the database and schema a connector
+//! points at.
+
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+use crate::DatabaseType;
+use crate::{ConnectionError, DbConnector};
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend};
+
+/// Quotes an identifier for `backend`, doubling any quote characters in it.
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+pub(crate) fn quote_ident(backend: DbBackend, name: &str) -> String {
+    match backend {
+        DbBackend::MySql => format!("`{}`", name.replace('`', "``")),
+        _ => format!("\"{}\"", name.replace('"', "\"\"")),
+    }
+}
+
+/// The database to connect to while the target database does not exist.
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+fn maintenance_database(db_type: DatabaseType) -> Option<&'static str> {
+    match db_type {
+        DatabaseType::PostgreSQL => Some("postgres"),
+        DatabaseType::MySQL | DatabaseType::MariaDB => Some("mysql"),
+        _ => None,
+    }
+}
+
+/// Connects to the maintenance database of the connector's server,
+/// returning the connection and the name of the connector's own database.
+/// `operation` names the caller in errors.
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+async fn connect_maintenance<'a>(
+    connector: &'a DbConnector,
+    operation: &str,
+) -> Result<(DatabaseConnection, &'a str), ConnectionError> {
+    let (Some(&db_type), Some(database)) = (connector.db_type(), connector.database_name()) else {
+        return Err(ConnectionError::InvalidConfig(format!(
+            "{operation} needs a database type and name"
+        )));
+    };
+    let maintenance = maintenance_database(db_type).ok_or_else(|| {
+        ConnectionError::InvalidConfig(format!("{operation} only applies to PostgreSQL and MySQL"))
+    })?;
+    if database == maintenance {
+        return Err(ConnectionError::InvalidConfig(format!(
+            "{operation} cannot be used on the {maintenance} maintenance database"
+        )));
+    }
+
+    let (conn, _) = connector
+        .maintenance_connector(maintenance)
+        .connect_any_host()
+        .await
+        .map_err(|e| e.context(&format!("maintenance database {maintenance}")))?;
+    Ok((conn, database))
+}
+
+/// Runs `CREATE DATABASE` for the connector's database from the
+/// maintenance database of its server.
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+pub(crate) async fn create_database(connector: &DbConnector) -> Result<(), ConnectionError> {
+    let (conn, database) = connect_maintenance(connector, "create_database_if_missing").await?;
+    let backend = conn.get_database_backend();
+    let result = conn
+        .execute_unprepared(&format!(
+            "CREATE DATABASE {}",
+            quote_ident(backend, database)
+        ))
+        .await;
+    let _ = conn.close().await;
+    result.map_err(|e| {
+        ConnectionError::DatabaseError(format!("Creating database {database} failed: {e}"))
+    })?;
+    log::info!("Created missing database {database}");
+    Ok(())
+}
+
+/// Runs `CREATE SCHEMA IF NOT EXISTS` for `schema`.
+#[cfg(feature = "postgres")]
+pub(crate) async fn ensure_schema(
+    conn: &DatabaseConnection,
+    schema: &str,
+) -> Result<(), ConnectionError> {
+    conn.execute_unprepared(&format!(
+        "CREATE SCHEMA IF NOT EXISTS {}",
+        quote_ident(DbBackend::Postgres, schema)
+    ))
+    .await
+    .map_err(|e| ConnectionError::DatabaseError(format!("Creating schema {schema} failed: {e}")))?;
+    Ok(())
+}
+
+/// Empties the connector's database, for test setup: drops and recreates
+/// it on PostgreSQL and MySQL, and truncates the file on SQLite.
+///
+/// Refuses unless the connector was built with
+/// [`allow_destructive()`](DbConnector::allow_destructive), so a
+/// misconfigured test run cannot wipe a real database. On PostgreSQL,
+/// other sessions on the database are terminated first; pools opened on it
+/// before the reset should be closed, or their connections will fail.
+pub async fn reset_database(connector: &DbConnector) -> Result<(), ConnectionError> {
+    if !connector.allow_destructive {
+        return Err(ConnectionError::InvalidConfig(
+            "reset_database drops all data; call allow_destructive() on the connector to permit it"
+                .into(),
+        ));
+    }
+
+    #[cfg(feature = "sqlite")]
+    if let Some(path) = connector.sqlite_file() {
+        return reset_sqlite_file(path);
+    }
+
+    #[cfg(any(feature = "postgres", feature = "mysql"))]
+    {
+        let (conn, database) = connect_maintenance(connector, "reset_database").await?;
+        let result = recreate(&conn, database).await;
+        let _ = conn.close().await;
+        result?;
+        log::info!("Reset database {database}");
+        Ok(())
+    }
+
+    #[cfg(not(any(feature = "postgres", feature = "mysql")))]
+    Err(ConnectionError::InvalidConfig(
+        "reset_database needs a SQLite database file".into(),
+    ))
+}
+
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+async fn recreate(conn: &DatabaseConnection, database: &str) -> Result<(), ConnectionError> {
+    let backend = conn.get_database_backend();
+    let quoted = quote_ident(backend, database);
+    let literal = format!("'{}'", database.replace('\'', "''"));
+    let failed = |e: sea_orm::DbErr| {
+        ConnectionError::DatabaseError(format!("Resetting database {database} failed: {e}"))
+    };
+
+    if backend == DbBackend::Postgres {
+        conn.execute_unprepared(&format!(
+            "SELECT pg_terminate_backend(pid) FROM pg_stat_activity \
+             WHERE datname = {literal} AND pid <> pg_backend_pid()"
+        ))
+        .await
+        .map_err(failed)?;
+    }
+    conn.execute_unprepared(&format!("DROP DATABASE IF EXISTS {quoted}"))
+        .await
+        .map_err(failed)?;
+    conn.execute_unprepared(&format!("CREATE DATABASE {quoted}"))
+        .await
+        .map_err(failed)?;
+    Ok(())
+}
+
+/// Truncates a SQLite database file to zero bytes, which SQLite opens as
+/// an empty database, and deletes its journal and WAL files.
+#[cfg(feature = "sqlite")]
+fn reset_sqlite_file(path: &str) -> Result<(), ConnectionError> {
+    if path == ":memory:" {
+        return Ok(());
+    }
+    let failed = |e: std::io::Error| {
+        ConnectionError::InvalidConfig(format!("Resetting SQLite database {path} failed: {e}"))
+    };
+    match std::fs::OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(path)
+    {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(failed(e)),
+    }
+    for suffix in ["-journal", "-wal", "-shm"] {
+        match std::fs::remove_file(format!("{path}{suffix}")) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(failed(e)),
+            _ => {}
+        }
+    }
+    log::info!("Reset SQLite database {path}");
+    Ok(())
+}
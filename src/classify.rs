@@ -0,0 +1,97 @@
+use sea_orm::{ConnAcquireErr, DbErr, RuntimeErr, sqlx};
+
+/// Whether an operation that failed is worth trying again, from
+/// [`classify`] or [`ConnectionError::class`](crate::ConnectionError::class).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorClass {
+    /// The transaction lost a conflict with another one (serialization
+    /// failure or deadlock) and was rolled back; running the whole
+    /// transaction again should succeed.
+    Conflict,
+    /// The server or network was temporarily unable to serve the request:
+    /// a dropped connection, a timeout, too many connections, a server
+    /// shutting down or a lock that could not be taken in time. Retrying
+    /// after a backoff may succeed.
+    Transient,
+    /// Retrying will fail the same way, e.g. a syntax error or a constraint
+    /// violation.
+    Permanent,
+}
+
+impl ErrorClass {
+    /// Whether running the operation again may succeed.
+    pub fn is_retryable(self) -> bool {
+        matches!(self, ErrorClass::Conflict | ErrorClass::Transient)
+    }
+
+    /// Whether the failure came from a temporary condition of the server or
+    /// network rather than from the operation itself.
+    pub fn is_transient(self) -> bool {
+        self == ErrorClass::Transient
+    }
+}
+
+/// Classifies an error from sea-orm by the driver's error code, for retry
+/// loops and circuit breakers.
+pub fn classify(err: &DbErr) -> ErrorClass {
+    let e = match err {
+        DbErr::ConnectionAcquire(ConnAcquireErr::Timeout | ConnAcquireErr::ConnectionClosed) => {
+            return ErrorClass::Transient;
+        }
+        DbErr::Conn(RuntimeErr::SqlxError(e))
+        | DbErr::Exec(RuntimeErr::SqlxError(e))
+        | DbErr::Query(RuntimeErr::SqlxError(e)) => e,
+        _ => return ErrorClass::Permanent,
+    };
+    match e {
+        sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut | sqlx::Error::WorkerCrashed => {
+            ErrorClass::Transient
+        }
+        sqlx::Error::Database(db) => classify_database_error(db.as_ref()),
+        _ => ErrorClass::Permanent,
+    }
+}
+
+fn classify_database_error(db: &dyn sqlx::error::DatabaseError) -> ErrorClass {
+    #[cfg(feature = "mysql")]
+    if let Some(mysql) = db.try_downcast_ref::<sqlx::mysql::MySqlDatabaseError>() {
+        return match mysql.number() {
+            // ER_LOCK_DEADLOCK; TiDB's write conflict
+            1213 | 9007 => ErrorClass::Conflict,
+            // ER_LOCK_WAIT_TIMEOUT, ER_CON_COUNT_ERROR, ER_SERVER_SHUTDOWN,
+            // ER_QUERY_INTERRUPTED, ER_QUERY_TIMEOUT, CR_SERVER_GONE_ERROR,
+            // CR_SERVER_LOST
+            1205 | 1040 | 1053 | 1317 | 3024 | 2006 | 2013 => ErrorClass::Transient,
+            _ => ErrorClass::Permanent,
+        };
+    }
+    #[cfg(feature = "sqlite")]
+    if db.try_downcast_ref::<sqlx::sqlite::SqliteError>().is_some() {
+        let code: i32 = db.code().and_then(|c| c.parse().ok()).unwrap_or_default();
+        return match code {
+            // SQLITE_BUSY_SNAPSHOT: the snapshot the transaction read is
+            // stale, so only starting over helps.
+            517 => ErrorClass::Conflict,
+            // SQLITE_BUSY, SQLITE_LOCKED and their extended codes
+            _ if matches!(code & 0xff, 5 | 6) => ErrorClass::Transient,
+            _ => ErrorClass::Permanent,
+        };
+    }
+    let Some(code) = db.code() else {
+        return ErrorClass::Permanent;
+    };
+    match code.as_ref() {
+        // serialization_failure, deadlock_detected
+        "40001" | "40P01" => ErrorClass::Conflict,
+        // lock_not_available, query_canceled (statement_timeout),
+        // insufficient_resources, too_many_connections, admin_shutdown,
+        // crash_shutdown, cannot_connect_now
+        "55P03" | "57014" | "53000" | "53300" | "57P01" | "57P02" | "57P03" => {
+            ErrorClass::Transient
+        }
+        // connection_exception and its subclasses
+        code if code.starts_with("08") => ErrorClass::Transient,
+        _ => ErrorClass::Permanent,
+    }
+}
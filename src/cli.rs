@@ -0,0 +1,143 @@
+use crate::{DatabaseType, DbConnector, SslMode, redact};
+use std::fmt;
+use std::path::PathBuf;
+
+/// The usual database flags for a command-line tool, to flatten into its
+/// own `clap` arguments:
+///
+/// ```ignore
+/// #[derive(clap::Parser)]
+/// struct Cli {
+///     #[command(flatten)]
+///     db: DbConnectorArgs,
+/// }
+///
+/// let conn = DbConnector::from(Cli::parse().db).connect().await?;
+/// ```
+///
+/// Each flag can also be given as a `DB_*` environment variable, named as
+/// for [`DbConnector::from_env`]: `--db-host` as `DB_HOST`, and so on.
+#[derive(Clone, Default, clap::Args)]
+#[command(about = None, long_about = None, next_help_heading = "Database")]
+pub struct DbConnectorArgs {
+    /// Database type: postgres, mysql, mariadb or sqlite
+    #[arg(long = "db-type", env = "DB_TYPE", value_name = "TYPE")]
+    pub db_type: Option<DatabaseType>,
+    /// Database host
+    #[arg(long = "db-host", env = "DB_HOST", value_name = "HOST")]
+    pub db_host: Option<String>,
+    /// Database port
+    #[arg(long = "db-port", env = "DB_PORT", value_name = "PORT")]
+    pub db_port: Option<u16>,
+    /// Database user
+    #[arg(long = "db-username", env = "DB_USERNAME", value_name = "USER")]
+    pub db_username: Option<String>,
+    /// Database password; prefer --db-password-file or DB_PASSWORD
+    #[arg(
+        long = "db-password",
+        env = "DB_PASSWORD",
+        value_name = "PASSWORD",
+        hide_env_values = true,
+        conflicts_with = "db_password_file"
+    )]
+    pub db_password: Option<String>,
+    /// File holding the database password
+    #[arg(
+        long = "db-password-file",
+        env = "DB_PASSWORD_FILE",
+        value_name = "PATH"
+    )]
+    pub db_password_file: Option<PathBuf>,
+    /// Database name, or the file path for SQLite
+    #[arg(long = "db-database", env = "DB_DATABASE", value_name = "NAME")]
+    pub db_database: Option<String>,
+    /// TLS mode: disable, prefer, require, verify-ca or verify-full
+    #[arg(long = "db-ssl-mode", env = "DB_SSL_MODE", value_name = "MODE")]
+    pub db_ssl_mode: Option<SslMode>,
+    /// Maximum number of pooled connections
+    #[arg(
+        long = "db-max-connections",
+        env = "DB_MAX_CONNECTIONS",
+        value_name = "N"
+    )]
+    pub db_max_connections: Option<u32>,
+    /// Minimum number of pooled connections
+    #[arg(
+        long = "db-min-connections",
+        env = "DB_MIN_CONNECTIONS",
+        value_name = "N"
+    )]
+    pub db_min_connections: Option<u32>,
+    /// Connect timeout in seconds
+    #[arg(
+        long = "db-connect-timeout",
+        env = "DB_CONNECT_TIMEOUT",
+        value_name = "SECS"
+    )]
+    pub db_connect_timeout: Option<u64>,
+}
+
+impl fmt::Debug for DbConnectorArgs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DbConnectorArgs")
+            .field("db_type", &self.db_type)
+            .field("db_host", &self.db_host)
+            .field("db_port", &self.db_port)
+            .field("db_username", &self.db_username)
+            .field(
+                "db_password",
+                &self.db_password.as_ref().map(|_| redact::MASK),
+            )
+            .field("db_password_file", &self.db_password_file)
+            .field("db_database", &self.db_database)
+            .field("db_ssl_mode", &self.db_ssl_mode)
+            .field("db_max_connections", &self.db_max_connections)
+            .field("db_min_connections", &self.db_min_connections)
+            .field("db_connect_timeout", &self.db_connect_timeout)
+            .finish()
+    }
+}
+
+impl From<DbConnectorArgs> for DbConnector {
+    fn from(args: DbConnectorArgs) -> Self {
+        let mut connector = DbConnector::new();
+        connector = match args.db_type {
+            Some(DatabaseType::PostgreSQL) => connector.postgres(),
+            Some(DatabaseType::MySQL) => connector.mysql(),
+            Some(DatabaseType::MariaDB) => connector.mariadb(),
+            Some(DatabaseType::SQLite) => connector.sqlite(),
+            None => connector,
+        };
+        if let Some(host) = args.db_host {
+            connector = connector.host(host);
+        }
+        if let Some(port) = args.db_port {
+            connector = connector.port(port);
+        }
+        if let Some(username) = args.db_username {
+            connector = connector.username(username);
+        }
+        if let Some(password) = args.db_password {
+            connector = connector.password(password);
+        }
+        if let Some(path) = args.db_password_file {
+            connector = connector.password_file(path);
+        }
+        if let Some(database) = args.db_database {
+            connector = connector.database(database);
+        }
+        if let Some(mode) = args.db_ssl_mode {
+            connector = connector.ssl_mode(mode);
+        }
+        if let Some(max) = args.db_max_connections {
+            connector = connector.max_connections(max);
+        }
+        if let Some(min) = args.db_min_connections {
+            connector = connector.min_connections(min);
+        }
+        if let Some(timeout) = args.db_connect_timeout {
+            connector = connector.connect_timeout(timeout);
+        }
+        connector
+    }
+}
@@ -0,0 +1,436 @@
+use crate::{ConnectionError, DbConnector, LoadBalancer, RoundRobin};
+use sea_orm::DatabaseConnection;
+use std::fmt;
+use std::sync::Arc;
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+use std::sync::Weak;
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// How often a discovered primary is checked.
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Clone)]
+struct Topology {
+    primary: DatabaseConnection,
+    replicas: Arc<[DatabaseConnection]>,
+}
+
+#[cfg_attr(not(any(feature = "postgres", feature = "mysql")), allow(dead_code))]
+struct Member {
+    connector: DbConnector,
+    conn: Option<DatabaseConnection>,
+}
+
+/// The writer and reader endpoints of a cluster whose roles are kept
+/// behind DNS names, as Aurora's are.
+#[cfg_attr(not(any(feature = "postgres", feature = "mysql")), allow(dead_code))]
+struct Endpoints {
+    writer: DbConnector,
+    reader: DbConnector,
+    /// Held while reconnecting, so a failover is handled once.
+    reconnecting: tokio::sync::Mutex<()>,
+}
+
+struct Shared {
+    topology: watch::Sender<Topology>,
+    /// Every configured host, when roles are discovered rather than fixed.
+    members: Option<tokio::sync::Mutex<Vec<Member>>>,
+    endpoints: Option<Endpoints>,
+}
+
+/// A primary with read replicas.
+///
+/// Writes, and reads that must see them, go to [`writer()`](Self::writer);
+/// other reads go to [`reader()`](Self::reader), which picks a replica with
+/// the cluster's [`LoadBalancer`] ([`RoundRobin`] unless set with
+/// [`load_balancer`](Self::load_balancer)). Without replicas, `reader()` is
+/// the primary. Clones share the same pools.
+///
+/// With [`discover`](Self::discover) the roles can change after a failover,
+/// so call `writer()` and `reader()` per unit of work rather than holding on
+/// to the returned handles.
+#[derive(Clone)]
+pub struct DbCluster {
+    shared: Arc<Shared>,
+    balancer: Arc<dyn LoadBalancer>,
+}
+
+impl fmt::Debug for DbCluster {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let topology = self.shared.topology.borrow();
+        f.debug_struct("DbCluster")
+            .field("primary", &topology.primary)
+            .field("replicas", &topology.replicas)
+            .field("discovery", &self.shared.members.is_some())
+            .field("endpoints", &self.shared.endpoints.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl DbCluster {
+    /// Connects the primary and every replica, failing with the first
+    /// error.
+    pub async fn connect<I>(primary: DbConnector, replicas: I) -> Result<Self, ConnectionError>
+    where
+        I: IntoIterator<Item = DbConnector>,
+    {
+        let primary = primary.connect().await.map_err(|e| e.context("primary"))?;
+        let mut connected = Vec::new();
+        for (idx, replica) in replicas.into_iter().enumerate() {
+            match replica.connect().await {
+                Ok(conn) => connected.push(conn),
+                Err(e) => {
+                    // Don't leave the pools opened so far running.
+                    let _ = primary.close().await;
+                    for conn in connected {
+                        let _ = conn.close().await;
+                    }
+                    return Err(e.context(&format!("replica {idx}")));
+                }
+            }
+        }
+        Ok(Self::with_shared(Shared {
+            topology: watch::Sender::new(Topology {
+                primary,
+                replicas: connected.into(),
+            }),
+            members: None,
+            endpoints: None,
+        }))
+    }
+
+    /// Connects to every host and asks each whether it is a replica
+    /// (`pg_is_in_recovery()` on Postgres, `@@global.read_only` on MySQL)
+    /// instead of taking the roles from the caller.
+    ///
+    /// Hosts that cannot be reached are skipped and retried on the next
+    /// discovery; at least one must be a primary. The primary is checked
+    /// every 5 seconds, and the roles are discovered again when it stops
+    /// answering or has been demoted. Call
+    /// [`rediscover`](Self::rediscover) to do so straight away, e.g. after a
+    /// write failed because the server was read-only.
+    #[cfg(any(feature = "postgres", feature = "mysql"))]
+    pub async fn discover<I>(hosts: I) -> Result<Self, ConnectionError>
+    where
+        I: IntoIterator<Item = DbConnector>,
+    {
+        let mut members: Vec<Member> = hosts
+            .into_iter()
+            .map(|connector| Member {
+                connector,
+                conn: None,
+            })
+            .collect();
+        let topology = discover_roles(&mut members).await?;
+        let cluster = Self::with_shared(Shared {
+            topology: watch::Sender::new(topology),
+            members: Some(tokio::sync::Mutex::new(members)),
+            endpoints: None,
+        });
+        spawn_monitor(Arc::downgrade(&cluster.shared));
+        Ok(cluster)
+    }
+
+    /// Connects to an Aurora PostgreSQL cluster through its cluster
+    /// (writer) endpoint and its reader endpoint, e.g.
+    /// `app.cluster-abc123.eu-west-1.rds.amazonaws.com` and
+    /// `app.cluster-ro-abc123.eu-west-1.rds.amazonaws.com`.
+    ///
+    /// `connector` supplies everything but the host: credentials, database,
+    /// pool settings. TLS is required unless it sets another `ssl_mode`.
+    ///
+    /// On failover Aurora promotes a reader and repoints the cluster
+    /// endpoint at it, but pooled connections stay on the old writer, now a
+    /// reader. The writer is therefore checked every 5 seconds; once it
+    /// stops answering or reports being a reader, both pools are closed and
+    /// reopened, resolving the endpoints again, until the cluster endpoint
+    /// leads to the new writer. [`rediscover`](Self::rediscover) does the
+    /// same straight away.
+    #[cfg(feature = "postgres")]
+    pub async fn aurora_postgres(
+        cluster_endpoint: &str,
+        reader_endpoint: &str,
+        connector: DbConnector,
+    ) -> Result<Self, ConnectionError> {
+        Self::aurora(
+            connector.aurora_endpoint(crate::DatabaseType::PostgreSQL, cluster_endpoint),
+            connector.aurora_endpoint(crate::DatabaseType::PostgreSQL, reader_endpoint),
+        )
+        .await
+    }
+
+    /// As [`aurora_postgres`](Self::aurora_postgres), for Aurora MySQL. A
+    /// reader is recognised by `@@innodb_read_only`.
+    #[cfg(feature = "mysql")]
+    pub async fn aurora_mysql(
+        cluster_endpoint: &str,
+        reader_endpoint: &str,
+        connector: DbConnector,
+    ) -> Result<Self, ConnectionError> {
+        Self::aurora(
+            connector.aurora_endpoint(crate::DatabaseType::MySQL, cluster_endpoint),
+            connector.aurora_endpoint(crate::DatabaseType::MySQL, reader_endpoint),
+        )
+        .await
+    }
+
+    #[cfg(any(feature = "postgres", feature = "mysql"))]
+    async fn aurora(writer: DbConnector, reader: DbConnector) -> Result<Self, ConnectionError> {
+        let endpoints = Endpoints {
+            writer,
+            reader,
+            reconnecting: tokio::sync::Mutex::new(()),
+        };
+        let topology = connect_endpoints(&endpoints).await?;
+        let cluster = Self::with_shared(Shared {
+            topology: watch::Sender::new(topology),
+            members: None,
+            endpoints: Some(endpoints),
+        });
+        spawn_monitor(Arc::downgrade(&cluster.shared));
+        Ok(cluster)
+    }
+
+    fn with_shared(shared: Shared) -> Self {
+        Self {
+            shared: Arc::new(shared),
+            balancer: Arc::new(RoundRobin::default()),
+        }
+    }
+
+    /// Replaces the strategy [`reader()`](Self::reader) uses to pick a
+    /// replica.
+    pub fn load_balancer(mut self, balancer: impl LoadBalancer) -> Self {
+        self.balancer = Arc::new(balancer);
+        self
+    }
+
+    /// The primary.
+    pub fn writer(&self) -> DatabaseConnection {
+        self.shared.topology.borrow().primary.clone()
+    }
+
+    /// The replica chosen by the load balancer, or the primary if there are
+    /// none.
+    pub fn reader(&self) -> DatabaseConnection {
+        let topology = self.shared.topology.borrow();
+        if topology.replicas.is_empty() {
+            return topology.primary.clone();
+        }
+        let idx = self.balancer.pick(&topology.replicas) % topology.replicas.len();
+        topology.replicas[idx].clone()
+    }
+
+    pub fn replicas(&self) -> Vec<DatabaseConnection> {
+        self.shared.topology.borrow().replicas.to_vec()
+    }
+
+    /// Asks every host for its role again and swaps in the new primary and
+    /// replicas. For an Aurora cluster, reopens both endpoints instead. On
+    /// error the current roles stay in place.
+    #[cfg(any(feature = "postgres", feature = "mysql"))]
+    pub async fn rediscover(&self) -> Result<(), ConnectionError> {
+        rediscover(&self.shared).await
+    }
+
+    /// Closes the primary and every replica, for all clones.
+    pub async fn close(&self) -> Result<(), ConnectionError> {
+        let conns: Vec<DatabaseConnection> = match &self.shared.members {
+            Some(members) => members
+                .lock()
+                .await
+                .iter_mut()
+                .filter_map(|member| member.conn.take())
+                .collect(),
+            None => {
+                let topology = self.shared.topology.borrow();
+                std::iter::once(&topology.primary)
+                    .chain(topology.replicas.iter())
+                    .cloned()
+                    .collect()
+            }
+        };
+        for conn in conns {
+            conn.close_by_ref()
+                .await
+                .map_err(|e| ConnectionError::DatabaseError(e.to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+async fn rediscover(shared: &Shared) -> Result<(), ConnectionError> {
+    if let Some(endpoints) = &shared.endpoints {
+        return reconnect_endpoints(shared, endpoints).await;
+    }
+    let Some(members) = &shared.members else {
+        return Err(ConnectionError::InvalidConfig(
+            "rediscover needs a cluster opened with DbCluster::discover()".into(),
+        ));
+    };
+    let mut members = members.lock().await;
+    let topology = discover_roles(&mut members).await?;
+    shared.topology.send_replace(topology);
+    Ok(())
+}
+
+/// Connects any member without a live pool and sorts the members into a
+/// primary and replicas. The first primary found wins.
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+async fn discover_roles(members: &mut [Member]) -> Result<Topology, ConnectionError> {
+    let mut primary = None;
+    let mut replicas = Vec::new();
+    let mut failures = Vec::new();
+    for (idx, member) in members.iter_mut().enumerate() {
+        let name = member
+            .connector
+            .host_name()
+            .map_or_else(|| format!("member {idx}"), str::to_string);
+        if member.conn.as_ref().is_some_and(crate::managed::is_closed) {
+            member.conn = None;
+        }
+        let conn = match &member.conn {
+            Some(conn) => conn.clone(),
+            None => match member.connector.clone().connect().await {
+                Ok(conn) => member.conn.insert(conn).clone(),
+                Err(e) => {
+                    log::warn!("Cluster member {name} is unreachable: {e}");
+                    failures.push(format!("{name}: {e}"));
+                    continue;
+                }
+            },
+        };
+        match crate::session::probe(&conn).await {
+            Ok(session) if !session.in_recovery && primary.is_none() => primary = Some(conn),
+            Ok(session) => {
+                if !session.in_recovery {
+                    log::warn!("Cluster member {name} is also a primary; using it as a replica");
+                }
+                replicas.push(conn);
+            }
+            Err(e) => {
+                log::warn!("Checking the role of cluster member {name} failed: {e}");
+                failures.push(format!("{name}: {e}"));
+            }
+        }
+    }
+    let primary = primary.ok_or_else(|| {
+        ConnectionError::ConnectionFailed(format!(
+            "No primary found among the cluster members ({})",
+            failures.join("; ")
+        ))
+    })?;
+    Ok(Topology {
+        primary,
+        replicas: replicas.into(),
+    })
+}
+
+/// Opens a pool on each endpoint, checking that the writer endpoint leads
+/// to the writer.
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+async fn connect_endpoints(endpoints: &Endpoints) -> Result<Topology, ConnectionError> {
+    let primary = endpoints
+        .writer
+        .clone()
+        .connect()
+        .await
+        .map_err(|e| e.context("writer endpoint"))?;
+    match is_primary(&primary, true).await {
+        Ok(true) => {}
+        Ok(false) => {
+            let _ = primary.close_by_ref().await;
+            return Err(ConnectionError::ConnectionFailed(
+                "The writer endpoint leads to a reader; failover is still in progress".into(),
+            ));
+        }
+        Err(e) => {
+            let _ = primary.close_by_ref().await;
+            return Err(e.context("writer endpoint"));
+        }
+    }
+    let replica = match endpoints.reader.clone().connect().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            let _ = primary.close_by_ref().await;
+            return Err(e.context("reader endpoint"));
+        }
+    };
+    Ok(Topology {
+        primary,
+        replicas: vec![replica].into(),
+    })
+}
+
+/// Replaces both endpoint pools with new ones, whose connections resolve
+/// the endpoint names again, and closes the old pools.
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+async fn reconnect_endpoints(
+    shared: &Shared,
+    endpoints: &Endpoints,
+) -> Result<(), ConnectionError> {
+    let _guard = endpoints.reconnecting.lock().await;
+    let topology = connect_endpoints(endpoints).await?;
+    let old = shared.topology.send_replace(topology);
+    for conn in std::iter::once(&old.primary).chain(old.replicas.iter()) {
+        let _ = conn.close_by_ref().await;
+    }
+    Ok(())
+}
+
+/// Whether `conn` is on the primary. On Aurora MySQL, where replicas do not
+/// set `read_only`, `innodb_read_only` is checked instead.
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+async fn is_primary(conn: &DatabaseConnection, aurora: bool) -> Result<bool, ConnectionError> {
+    use sea_orm::{ConnectionTrait, DbBackend, Statement};
+
+    let backend = conn.get_database_backend();
+    if !(aurora && backend == DbBackend::MySql) {
+        return crate::session::probe(conn)
+            .await
+            .map(|session| !session.in_recovery);
+    }
+    let sql = "SELECT CAST(@@global.innodb_read_only AS SIGNED)";
+    let row = conn
+        .query_one(Statement::from_string(backend, sql))
+        .await
+        .map_err(|e| ConnectionError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| ConnectionError::DatabaseError("Role query returned no rows".into()))?;
+    row.try_get_by_index::<i64>(0)
+        .map(|read_only| read_only == 0)
+        .map_err(|e| ConnectionError::DatabaseError(e.to_string()))
+}
+
+/// Checks the primary's role while any handle to the cluster is alive,
+/// rediscovering when the check fails or the primary reports being a
+/// replica.
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+fn spawn_monitor(shared: Weak<Shared>) {
+    crate::tasks::spawn("tin-sea-conn::cluster-discovery", async move {
+        loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            let Some(shared) = shared.upgrade() else {
+                return;
+            };
+            let primary = shared.topology.borrow().primary.clone();
+            if crate::managed::is_closed(&primary) {
+                return;
+            }
+            let aurora = shared.endpoints.is_some();
+            match tokio::time::timeout(CHECK_INTERVAL, is_primary(&primary, aurora)).await {
+                Ok(Ok(true)) => continue,
+                Ok(Ok(false)) => log::warn!("The cluster primary has become a replica"),
+                Ok(Err(e)) => log::warn!("Checking the cluster primary failed: {e}"),
+                Err(_) => log::warn!("Checking the cluster primary timed out"),
+            }
+            match rediscover(&shared).await {
+                Ok(()) => log::info!("Rediscovered the cluster roles"),
+                Err(e) => log::warn!("Rediscovering the cluster roles failed: {e}"),
+            }
+        }
+    });
+}
@@ -0,0 +1,401 @@
+use crate::{ConnectionError, DbConnector, redact};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+/// Connector settings as they appear in a configuration file.
+///
+/// Every field is optional; unset fields keep the `DbConnector` defaults.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DbConfig {
+    /// `postgres`, `mysql`, `mariadb` or `sqlite`.
+    #[serde(rename = "type")]
+    pub db_type: Option<String>,
+    pub host: Option<String>,
+    /// Hosts to fail over between, in order. Takes precedence over `host`.
+    pub hosts: Option<Vec<String>>,
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub password_file: Option<String>,
+    pub database: Option<String>,
+    pub ssl_mode: Option<String>,
+    pub ssl_root_cert: Option<String>,
+    pub ssl_client_cert: Option<String>,
+    pub ssl_client_key: Option<String>,
+    pub channel_binding: Option<String>,
+    pub gss_enc_mode: Option<String>,
+    pub target_session_attrs: Option<String>,
+    pub load_balance_hosts: Option<bool>,
+    pub max_connections: Option<u32>,
+    pub min_connections: Option<u32>,
+    pub connect_timeout: Option<u64>,
+    pub idle_timeout: Option<u64>,
+    pub max_lifetime: Option<u64>,
+    pub test_before_acquire: Option<bool>,
+    pub sqlx_logging: Option<bool>,
+    pub pooler_compat: Option<bool>,
+    pub warm_up: Option<bool>,
+    /// Statements after which a managed pool is replaced.
+    pub max_uses_per_connection: Option<u64>,
+    /// Failed statements after which a managed pool is replaced.
+    pub retire_after_errors: Option<u32>,
+    /// Hold time, in milliseconds, above which an acquired connection is
+    /// reported as leaked.
+    pub leak_detection_threshold_ms: Option<u64>,
+    /// Create the database on connect when it does not exist.
+    pub create_database_if_missing: Option<bool>,
+    /// Schema created after connecting (Postgres).
+    pub ensure_schema: Option<String>,
+    /// Use `ensure_schema` as the search path.
+    pub schema_search_path: Option<bool>,
+    /// Directory of `.sql` files run after connecting.
+    pub seed_sql_dir: Option<String>,
+    /// Run the seed files only when the database was just created.
+    pub seed_only_if_created: Option<bool>,
+    /// Percentage of `max_connections` in use at which to warn.
+    pub pool_saturation_warning: Option<u8>,
+    /// Acquire wait, in milliseconds, above which to warn.
+    pub acquire_wait_warning_ms: Option<u64>,
+    /// `text` or `json`, for the connection audit log.
+    pub log_format: Option<String>,
+    /// Session variables set on every new connection.
+    pub session_vars: Option<BTreeMap<String, String>>,
+    /// SQL run on every new connection.
+    pub after_connect: Option<Vec<String>>,
+}
+
+impl fmt::Debug for DbConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DbConfig")
+            .field("db_type", &self.db_type)
+            .field("host", &self.host)
+            .field("hosts", &self.hosts)
+            .field("port", &self.port)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| redact::MASK))
+            .field("password_file", &self.password_file)
+            .field("database", &self.database)
+            .field("ssl_mode", &self.ssl_mode)
+            .field("ssl_root_cert", &self.ssl_root_cert)
+            .field("ssl_client_cert", &self.ssl_client_cert)
+            .field("ssl_client_key", &self.ssl_client_key)
+            .field("channel_binding", &self.channel_binding)
+            .field("gss_enc_mode", &self.gss_enc_mode)
+            .field("target_session_attrs", &self.target_session_attrs)
+            .field("load_balance_hosts", &self.load_balance_hosts)
+            .field("max_connections", &self.max_connections)
+            .field("min_connections", &self.min_connections)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("idle_timeout", &self.idle_timeout)
+            .field("max_lifetime", &self.max_lifetime)
+            .field("test_before_acquire", &self.test_before_acquire)
+            .field("sqlx_logging", &self.sqlx_logging)
+            .field("pooler_compat", &self.pooler_compat)
+            .field("warm_up", &self.warm_up)
+            .field("max_uses_per_connection", &self.max_uses_per_connection)
+            .field("retire_after_errors", &self.retire_after_errors)
+            .field(
+                "leak_detection_threshold_ms",
+                &self.leak_detection_threshold_ms,
+            )
+            .field(
+                "create_database_if_missing",
+                &self.create_database_if_missing,
+            )
+            .field("ensure_schema", &self.ensure_schema)
+            .field("schema_search_path", &self.schema_search_path)
+            .field("seed_sql_dir", &self.seed_sql_dir)
+            .field("seed_only_if_created", &self.seed_only_if_created)
+            .field("pool_saturation_warning", &self.pool_saturation_warning)
+            .field("acquire_wait_warning_ms", &self.acquire_wait_warning_ms)
+            .field("log_format", &self.log_format)
+            .field("session_vars", &self.session_vars)
+            .field("after_connect", &self.after_connect)
+            .finish()
+    }
+}
+
+/// Names the file [`DbConnector::profile`] reads, `db.toml` by default.
+const CONFIG_FILE_VAR: &str = "DB_CONFIG_FILE";
+const DEFAULT_CONFIG_FILE: &str = "db.toml";
+/// Names the profile [`DbConnector::from_profile_env`] selects.
+const PROFILE_VAR: &str = "DB_PROFILE";
+
+/// Profiles are kept as tables until `[defaults]` is merged into them, so a
+/// setting can come from either.
+#[derive(Debug, Deserialize)]
+struct ProfilesFile {
+    #[serde(default)]
+    defaults: toml::Table,
+    #[serde(default)]
+    profiles: BTreeMap<String, toml::Table>,
+}
+
+/// Overlays `overrides` on `base`. Nested tables such as `session_vars` are
+/// merged key by key; anything else is replaced.
+fn merge(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overrides)) => {
+                merge(base, overrides)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+impl TryFrom<DbConfig> for DbConnector {
+    type Error = ConnectionError;
+
+    fn try_from(config: DbConfig) -> Result<Self, Self::Error> {
+        let mut connector = DbConnector::new();
+        if let Some(db_type) = &config.db_type {
+            connector = connector.db_type_name(db_type)?;
+        }
+        if let Some(host) = config.host {
+            connector = connector.host(host);
+        }
+        if let Some(hosts) = config.hosts {
+            connector = connector.hosts(hosts);
+        }
+        if let Some(port) = config.port {
+            connector = connector.port(port);
+        }
+        if let Some(username) = config.username {
+            connector = connector.username(username);
+        }
+        if let Some(password) = config.password {
+            connector = connector.password(password);
+        }
+        if let Some(path) = config.password_file {
+            connector = connector.password_file(path);
+        }
+        if let Some(database) = config.database {
+            connector = connector.database(database);
+        }
+        if let Some(mode) = config.ssl_mode {
+            connector = connector.ssl_mode(mode.parse()?);
+        }
+        if let Some(path) = config.ssl_root_cert {
+            connector = connector.ssl_root_cert(path);
+        }
+        if let Some(path) = config.ssl_client_cert {
+            connector = connector.ssl_client_cert(path);
+        }
+        if let Some(path) = config.ssl_client_key {
+            connector = connector.ssl_client_key(path);
+        }
+        if let Some(attrs) = config.target_session_attrs {
+            connector = connector.target_session_attrs(attrs.parse()?);
+        }
+        if let Some(enabled) = config.load_balance_hosts {
+            connector = connector.load_balance_hosts(enabled);
+        }
+        if let Some(mode) = config.channel_binding {
+            connector = connector.channel_binding(mode.parse()?);
+        }
+        if let Some(mode) = config.gss_enc_mode {
+            connector = connector.gss_enc_mode(mode.parse()?);
+        }
+        if let Some(max) = config.max_connections {
+            connector = connector.max_connections(max);
+        }
+        if let Some(min) = config.min_connections {
+            connector = connector.min_connections(min);
+        }
+        if let Some(timeout) = config.connect_timeout {
+            connector = connector.connect_timeout(timeout);
+        }
+        if let Some(timeout) = config.idle_timeout {
+            connector = connector.idle_timeout(timeout);
+        }
+        if let Some(lifetime) = config.max_lifetime {
+            connector = connector.max_lifetime(lifetime);
+        }
+        if let Some(test) = config.test_before_acquire {
+            connector = connector.test_before_acquire(test);
+        }
+        if let Some(logging) = config.sqlx_logging {
+            connector = connector.sqlx_logging(logging);
+        }
+        if let Some(enabled) = config.pooler_compat {
+            connector = connector.pooler_compat(enabled);
+        }
+        if let Some(enabled) = config.warm_up {
+            connector = connector.warm_up(enabled);
+        }
+        if let Some(uses) = config.max_uses_per_connection {
+            connector = connector.max_uses_per_connection(uses);
+        }
+        if let Some(errors) = config.retire_after_errors {
+            connector = connector.retire_after_errors(errors);
+        }
+        if let Some(ms) = config.leak_detection_threshold_ms {
+            connector = connector.leak_detection_threshold(Duration::from_millis(ms));
+        }
+        if let Some(enabled) = config.create_database_if_missing {
+            connector = connector.create_database_if_missing(enabled);
+        }
+        if let Some(schema) = config.ensure_schema {
+            connector = connector.ensure_schema(schema);
+        }
+        if let Some(enabled) = config.schema_search_path {
+            connector = connector.schema_search_path(enabled);
+        }
+        if let Some(dir) = config.seed_sql_dir {
+            connector = connector.seed_sql_dir(dir);
+        }
+        if let Some(enabled) = config.seed_only_if_created {
+            connector = connector.seed_only_if_created(enabled);
+        }
+        if let Some(percent) = config.pool_saturation_warning {
+            connector = connector.pool_saturation_warning(percent);
+        }
+        if let Some(ms) = config.acquire_wait_warning_ms {
+            connector = connector.acquire_wait_warning(Duration::from_millis(ms));
+        }
+        if let Some(format) = config.log_format {
+            connector = connector.log_format(format.parse()?);
+        }
+        if let Some(vars) = config.session_vars {
+            connector = connector.session_vars(vars);
+        }
+        if let Some(statements) = config.after_connect {
+            connector = connector.after_connect(statements);
+        }
+        Ok(connector)
+    }
+}
+
+fn read_profiles(path: &Path) -> Result<BTreeMap<String, DbConfig>, ConnectionError> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        ConnectionError::InvalidConfig(format!(
+            "Failed to read config file {}: {e}",
+            path.display()
+        ))
+    })?;
+    parse_profiles(&content, path)
+}
+
+/// Parses the profiles of a TOML file already read from `path`.
+pub(crate) fn parse_profiles(
+    content: &str,
+    path: &Path,
+) -> Result<BTreeMap<String, DbConfig>, ConnectionError> {
+    let file: ProfilesFile = toml::from_str(content).map_err(|e| {
+        ConnectionError::InvalidConfig(format!("Invalid config file {}: {e}", path.display()))
+    })?;
+    file.profiles
+        .into_iter()
+        .map(|(name, table)| {
+            let mut merged = file.defaults.clone();
+            merge(&mut merged, table);
+            let config = toml::Value::Table(merged).try_into().map_err(|e| {
+                ConnectionError::InvalidConfig(format!(
+                    "Invalid profile '{name}' in {}: {e}",
+                    path.display()
+                ))
+            })?;
+            Ok((name, config))
+        })
+        .collect()
+}
+
+/// Reads the `[profiles.<name>]` table of a TOML file.
+pub(crate) fn read_profile(path: &Path, name: &str) -> Result<DbConfig, ConnectionError> {
+    read_profiles(path)?.remove(name).ok_or_else(|| {
+        ConnectionError::InvalidConfig(format!("Profile '{name}' not found in {}", path.display()))
+    })
+}
+
+impl DbConnector {
+    /// Build a connector from the `[profiles.<name>]` table of a TOML file,
+    /// on top of the file's `[defaults]` table if it has one.
+    pub fn from_profile<P: AsRef<Path>>(path: P, name: &str) -> Result<Self, ConnectionError> {
+        read_profile(path.as_ref(), name)?.try_into()
+    }
+
+    /// Build a connector from the profile `name` (e.g. `"staging"`) of the
+    /// config file named by `DB_CONFIG_FILE`, or `db.toml` in the working
+    /// directory.
+    pub fn profile(name: &str) -> Result<Self, ConnectionError> {
+        let path = std::env::var(CONFIG_FILE_VAR).unwrap_or_else(|_| DEFAULT_CONFIG_FILE.into());
+        Self::from_profile(path, name)
+    }
+
+    /// As [`profile`](Self::profile), for the profile named by `DB_PROFILE`,
+    /// so one build can run as `dev`, `staging` or `prod`.
+    pub fn from_profile_env() -> Result<Self, ConnectionError> {
+        let name = std::env::var(PROFILE_VAR)
+            .map_err(|_| ConnectionError::InvalidConfig(format!("{PROFILE_VAR} is not set")))?;
+        Self::profile(&name)
+    }
+
+    /// Build a connector for every `[profiles.*]` table in a TOML file, keyed
+    /// by profile name, each on top of `[defaults]`.
+    pub fn load_profiles<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<BTreeMap<String, DbConnector>, ConnectionError> {
+        read_profiles(path.as_ref())?
+            .into_iter()
+            .map(|(name, config)| Ok((name, config.try_into()?)))
+            .collect()
+    }
+}
+
+/// Lets a [`DbConfig`] take part in an application's layered figment
+/// configuration, e.g. as defaults beneath a TOML file and `DB_*`
+/// variables:
+///
+/// ```ignore
+/// let figment = Figment::from(DbConfig { max_connections: Some(20), ..Default::default() })
+///     .merge(Toml::file("database.toml"))
+///     .merge(Env::prefixed("DB_"));
+/// let connector = DbConnector::from_figment(&figment)?;
+/// ```
+///
+/// Unset fields are left out, so they do not hide values from other
+/// layers.
+#[cfg(feature = "figment")]
+impl figment::Provider for DbConfig {
+    fn metadata(&self) -> figment::Metadata {
+        figment::Metadata::named("tin-sea-conn DbConfig")
+    }
+
+    fn data(
+        &self,
+    ) -> Result<figment::value::Map<figment::Profile, figment::value::Dict>, figment::Error> {
+        let mut data = figment::providers::Serialized::defaults(self).data()?;
+        for dict in data.values_mut() {
+            dict.retain(|_, value| !matches!(value, figment::value::Value::Empty(..)));
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(feature = "figment")]
+impl DbConnector {
+    /// Build a connector from the settings a figment extracts to, named as
+    /// in [`DbConfig`].
+    pub fn from_figment(figment: &figment::Figment) -> Result<Self, ConnectionError> {
+        figment
+            .extract::<DbConfig>()
+            .map_err(|e| ConnectionError::InvalidConfig(e.to_string()))?
+            .try_into()
+    }
+
+    /// As [`from_figment`](Self::from_figment), for the settings nested
+    /// under `key` (e.g. `"database"`) of an application's configuration.
+    pub fn from_figment_at(figment: &figment::Figment, key: &str) -> Result<Self, ConnectionError> {
+        figment
+            .extract_inner::<DbConfig>(key)
+            .map_err(|e| ConnectionError::InvalidConfig(e.to_string()))?
+            .try_into()
+    }
+}
@@ -0,0 +1,66 @@
+use crate::{ConnectionError, redact};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+
+/// A username/password pair.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &redact::MASK)
+            .finish()
+    }
+}
+
+/// A source of database credentials, queried each time a connector
+/// connects. Implement it to plug in a secret backend this crate does not
+/// know about (a KMS, an internal API, the OS keychain):
+///
+/// ```ignore
+/// struct Keychain;
+///
+/// impl CredentialProvider for Keychain {
+///     async fn get(&self) -> Result<Credentials, ConnectionError> {
+///         Ok(Credentials { username: "app".into(), password: read_keychain().await? })
+///     }
+/// }
+///
+/// let conn = DbConnector::new().postgres().credential_provider(Keychain);
+/// ```
+///
+/// Fixed [`Credentials`] are themselves a provider, equivalent to setting
+/// `username()` and `password()`.
+pub trait CredentialProvider: Send + Sync + 'static {
+    fn get(&self) -> impl Future<Output = Result<Credentials, ConnectionError>> + Send;
+}
+
+impl CredentialProvider for Credentials {
+    async fn get(&self) -> Result<Credentials, ConnectionError> {
+        Ok(self.clone())
+    }
+}
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Object-safe form of [`CredentialProvider`], so the builder can store any
+/// provider behind an `Arc`.
+#[cfg_attr(
+    not(any(feature = "postgres", feature = "mysql", feature = "sqlite")),
+    allow(dead_code)
+)]
+pub(crate) trait DynCredentialProvider: Send + Sync {
+    fn get_boxed(&self) -> BoxFuture<'_, Result<Credentials, ConnectionError>>;
+}
+
+impl<T: CredentialProvider> DynCredentialProvider for T {
+    fn get_boxed(&self) -> BoxFuture<'_, Result<Credentials, ConnectionError>> {
+        Box::pin(self.get())
+    }
+}
@@ -0,0 +1,61 @@
+/// Every setting a [`DbConnector`](crate::DbConnector) would connect with,
+/// defaults included, from
+/// [`effective_config`](crate::DbConnector::effective_config).
+///
+/// Safe to log: the password is never included, only where it comes from.
+/// With the `serde` feature (enabled by `config`) it implements
+/// `Serialize`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct EffectiveConfig {
+    /// `postgresql`, `mysql` or `sqlite`.
+    pub db_type: Option<&'static str>,
+    /// The connection URL with the password masked, or `None` while
+    /// required settings are missing.
+    pub url: Option<String>,
+    pub host: Option<String>,
+    /// Hosts tried in order after `host`.
+    pub fallback_hosts: Vec<String>,
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    /// Where the password comes from, named after the builder method that
+    /// set it up, e.g. `password`, `password_file` or `aws_iam_auth`.
+    pub password_source: Option<&'static str>,
+    pub database: Option<String>,
+    /// Unix socket connected through instead of TCP.
+    pub unix_socket: Option<String>,
+    pub ssl_mode: Option<&'static str>,
+    pub ssl_root_cert: Option<String>,
+    pub ssl_client_cert: Option<String>,
+    pub ssl_client_key: Option<String>,
+    pub channel_binding: Option<&'static str>,
+    pub gss_enc_mode: Option<&'static str>,
+    pub target_session_attrs: Option<&'static str>,
+    pub load_balance_hosts: bool,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// Seconds.
+    pub connect_timeout: u64,
+    /// Seconds.
+    pub idle_timeout: u64,
+    /// Seconds.
+    pub max_lifetime: u64,
+    pub test_before_acquire: bool,
+    pub sqlx_logging: bool,
+    pub pooler_compat: bool,
+    pub warm_up: bool,
+    pub max_uses_per_connection: Option<u64>,
+    pub retire_after_errors: Option<u32>,
+    pub leak_detection_threshold_ms: Option<u64>,
+    pub create_database_if_missing: bool,
+    pub ensure_schema: Option<String>,
+    pub schema_search_path: bool,
+    pub seed_sql_dir: Option<String>,
+    pub seed_only_if_created: bool,
+    pub allow_destructive: bool,
+    pub pool_saturation_warning: Option<u8>,
+    pub acquire_wait_warning_ms: Option<u64>,
+    pub session_vars: Vec<(String, String)>,
+    pub after_connect: Vec<String>,
+}
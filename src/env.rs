@@ -0,0 +1,228 @@
+use crate::{ConnectionError, DbConnector, SslMode};
+use std::path::Path;
+
+/// Reads a secret from a mounted file, stripping the trailing newline most
+/// secret tooling leaves behind.
+pub(crate) fn read_secret_file(path: &Path) -> Result<String, ConnectionError> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        ConnectionError::InvalidConfig(format!(
+            "Failed to read secret file {}: {e}",
+            path.display()
+        ))
+    })?;
+    Ok(content.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Reads `name`, or the contents of the file named by `name_FILE`.
+///
+/// Setting both is rejected, matching the official database images.
+fn var(name: &str) -> Result<Option<String>, ConnectionError> {
+    let file_name = format!("{name}_FILE");
+    match (std::env::var(name).ok(), std::env::var(&file_name).ok()) {
+        (Some(_), Some(_)) => Err(ConnectionError::InvalidConfig(format!(
+            "Both {name} and {file_name} are set"
+        ))),
+        (Some(value), None) => Ok(Some(value)),
+        (None, Some(path)) => read_secret_file(Path::new(&path)).map(Some),
+        (None, None) => Ok(None),
+    }
+}
+
+fn parse_var<T: std::str::FromStr>(name: &str) -> Result<Option<T>, ConnectionError> {
+    var(name)?
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| ConnectionError::InvalidConfig(format!("Invalid {name}: {value}")))
+        })
+        .transpose()
+}
+
+/// Loads `path` into the environment, leaving variables that are already
+/// set alone. A missing file is skipped.
+#[cfg(feature = "dotenv")]
+fn load_dotenv(path: &str) -> Result<(), ConnectionError> {
+    match dotenvy::from_path(path) {
+        Err(e) if !e.not_found() => Err(ConnectionError::InvalidConfig(format!(
+            "Failed to load {path}: {e}"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+impl DbConnector {
+    /// Build a connector from `DB_*` environment variables.
+    ///
+    /// Reads `DB_TYPE` (`postgres`, `mysql`, `mariadb` or `sqlite`), `DB_HOST`,
+    /// `DB_PORT`, `DB_USERNAME`, `DB_PASSWORD`, `DB_DATABASE`, `DB_SSL_MODE`,
+    /// `DB_MAX_CONNECTIONS` and `DB_MIN_CONNECTIONS`. Each variable may instead
+    /// be given as `<NAME>_FILE` pointing at a mounted secret file; for
+    /// `DB_PASSWORD_FILE` the file is read at connect time so rotated secrets
+    /// are picked up.
+    pub fn from_env() -> Result<Self, ConnectionError> {
+        let mut connector = DbConnector::new();
+
+        if let Some(db_type) = var("DB_TYPE")? {
+            connector = connector.db_type_name(&db_type)?;
+        }
+        if let Some(host) = var("DB_HOST")? {
+            connector = connector.host(host);
+        }
+        if let Some(port) = parse_var("DB_PORT")? {
+            connector = connector.port(port);
+        }
+        if let Some(username) = var("DB_USERNAME")? {
+            connector = connector.username(username);
+        }
+        match (
+            std::env::var("DB_PASSWORD").ok(),
+            std::env::var("DB_PASSWORD_FILE").ok(),
+        ) {
+            (Some(_), Some(_)) => {
+                return Err(ConnectionError::InvalidConfig(
+                    "Both DB_PASSWORD and DB_PASSWORD_FILE are set".into(),
+                ));
+            }
+            (Some(password), None) => connector = connector.password(password),
+            (None, Some(path)) => connector = connector.password_file(path),
+            (None, None) => {}
+        }
+        if let Some(database) = var("DB_DATABASE")? {
+            connector = connector.database(database);
+        }
+        if let Some(mode) = parse_var::<SslMode>("DB_SSL_MODE")? {
+            connector = connector.ssl_mode(mode);
+        }
+        if let Some(max) = parse_var("DB_MAX_CONNECTIONS")? {
+            connector = connector.max_connections(max);
+        }
+        if let Some(min) = parse_var("DB_MIN_CONNECTIONS")? {
+            connector = connector.min_connections(min);
+        }
+
+        Ok(connector)
+    }
+
+    /// As [`from_env`](Self::from_env), after loading `.env.local` and then
+    /// `.env` from the working directory into the environment, as most web
+    /// frameworks do in development. Variables already set win over both
+    /// files, and `.env.local` wins over `.env`; missing files are skipped.
+    #[cfg(feature = "dotenv")]
+    pub fn from_dotenv() -> Result<Self, ConnectionError> {
+        load_dotenv(".env.local")?;
+        load_dotenv(".env")?;
+        Self::from_env()
+    }
+}
+
+/// Hosting platforms whose database environment conventions are known.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    /// Heroku Postgres: `DATABASE_URL`, TLS required, 20-connection limit on
+    /// the Essential plans shared by all dynos.
+    Heroku,
+    /// Render: `DATABASE_URL`; external hostnames (`*.render.com`) require
+    /// TLS, internal ones do not. Free instances allow about 100 connections.
+    Render,
+    /// Fly.io: `DATABASE_URL` pointing at a private `.flycast` or `.internal`
+    /// address, where the WireGuard network already encrypts traffic.
+    Fly,
+    /// Railway: `DATABASE_PRIVATE_URL` when available, else `DATABASE_URL`.
+    Railway,
+}
+
+impl DbConnector {
+    /// Build a connector from the environment conventions of a PaaS.
+    ///
+    /// The platform's database URL is parsed with [`DbConnector::from_url`],
+    /// then TLS and pool size defaults suited to the platform are applied
+    /// wherever the URL did not say otherwise.
+    pub fn from_platform_env(platform: Platform) -> Result<Self, ConnectionError> {
+        let names: &[&str] = match platform {
+            Platform::Railway => &["DATABASE_PRIVATE_URL", "DATABASE_URL"],
+            _ => &["DATABASE_URL"],
+        };
+        let url = names
+            .iter()
+            .find_map(|name| std::env::var(name).ok().filter(|v| !v.is_empty()))
+            .ok_or_else(|| {
+                ConnectionError::InvalidConfig(format!("{} is not set", names.join(" or ")))
+            })?;
+
+        let connector = DbConnector::from_url(&url)?;
+        let host = connector.host_name().unwrap_or_default().to_string();
+
+        let (ssl_mode, max_connections) = match platform {
+            Platform::Heroku => (SslMode::Require, 5),
+            Platform::Render if host.ends_with(".render.com") => (SslMode::Require, 10),
+            Platform::Render => (SslMode::Prefer, 10),
+            Platform::Fly => (SslMode::Disable, 10),
+            Platform::Railway if host.ends_with(".railway.internal") => (SslMode::Disable, 10),
+            Platform::Railway => (SslMode::Require, 10),
+        };
+
+        let connector = connector.max_connections(max_connections);
+        Ok(if connector.ssl_mode_value().is_some() {
+            connector
+        } else {
+            connector.ssl_mode(ssl_mode)
+        })
+    }
+
+    /// Build a connector from `DATABASE_URL`, the way PaaS hosts hand it out.
+    ///
+    /// The URL is parsed with [`DbConnector::from_url`], which accepts the
+    /// `postgres://` scheme Heroku and Render use as well as `postgresql://`.
+    /// When the host is not local, TLS is required unless the URL sets
+    /// `sslmode` itself. Local hosts are loopback addresses, Unix sockets,
+    /// single-label names such as a Compose service or Render internal host,
+    /// and names under `.local`, `.localhost` or `.internal`. Use
+    /// [`from_database_url_env_with`](Self::from_database_url_env_with) for
+    /// another mode, or none.
+    pub fn from_database_url_env() -> Result<Self, ConnectionError> {
+        Self::from_database_url_env_with(Some(SslMode::Require))
+    }
+
+    /// As [`from_database_url_env`](Self::from_database_url_env), applying
+    /// `remote_ssl_mode` to non-local hosts instead of `require`. `None`
+    /// leaves TLS to the URL and the driver defaults.
+    pub fn from_database_url_env_with(
+        remote_ssl_mode: Option<SslMode>,
+    ) -> Result<Self, ConnectionError> {
+        let url = std::env::var("DATABASE_URL")
+            .ok()
+            .filter(|v| !v.is_empty())
+            .ok_or_else(|| ConnectionError::InvalidConfig("DATABASE_URL is not set".into()))?;
+        let connector = DbConnector::from_url(&url)?;
+
+        match (remote_ssl_mode, connector.host_name()) {
+            (Some(mode), Some(host))
+                if connector.ssl_mode_value().is_none() && !is_local_host(host) =>
+            {
+                Ok(connector.ssl_mode(mode))
+            }
+            _ => Ok(connector),
+        }
+    }
+}
+
+/// Whether `host`, possibly with a `:port`, is reached without leaving the
+/// machine or a private network that does not expect TLS.
+fn is_local_host(host: &str) -> bool {
+    if host.starts_with('/') {
+        return true;
+    }
+    let name = match host.strip_prefix('[') {
+        Some(inner) => inner.split_once(']').map_or(inner, |(name, _)| name),
+        None if host.matches(':').count() == 1 => host.split_once(':').map_or(host, |(n, _)| n),
+        None => host,
+    };
+    if let Ok(ip) = name.parse::<std::net::IpAddr>() {
+        return ip.is_loopback();
+    }
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+    !name.contains('.')
+        || [".local", ".localhost", ".internal"]
+            .iter()
+            .any(|suffix| name.ends_with(suffix))
+}
@@ -1,21 +1,413 @@
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+use sea_orm::{ConnAcquireErr, DbErr, RuntimeErr, sqlx};
 use std::fmt;
+use std::time::Duration;
 
 // 定义自己的错误类型
-#[derive(Debug)]
+//
+// Failures to connect that callers commonly need to tell apart get their
+// own variant and keep the driver's error as `source()`; everything else
+// is described by a message. Match on [`code()`](ConnectionError::code)
+// where a stable value is needed, e.g. as a metric label.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum ConnectionError {
+    #[error("Configuration error: {0}")]
     InvalidConfig(String),
+    /// A setting needed to build the connection URL was not given.
+    #[error("Configuration error: {0} is required")]
+    MissingField(ConfigField),
+    #[error("Connection failed: {0}")]
     ConnectionFailed(String),
+    #[error("Database error: {0}")]
     DatabaseError(String),
+    /// A query did not finish within the deadline set with
+    /// [`with_timeout`](crate::with_timeout).
+    #[error("Query timed out after {0:?}")]
+    QueryTimeout(Duration),
+    /// The pool has been closed, or is shutting down, and hands out no
+    /// more connections.
+    #[error("The connection pool is closed")]
+    PoolClosed,
+    /// The server rejected the username or password.
+    #[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+    #[error("Authentication failed: {message}")]
+    AuthenticationFailed { message: String, source: DbErr },
+    /// TLS could not be negotiated, e.g. the certificate did not verify or
+    /// the server does not offer TLS.
+    #[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+    #[error("TLS handshake failed: {message}")]
+    TlsHandshakeFailed { message: String, source: DbErr },
+    /// Connecting did not succeed within the connect timeout; `elapsed` is
+    /// how long was waited.
+    #[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+    #[error("Connecting timed out after {elapsed:?}: {message}")]
+    Timeout {
+        elapsed: Duration,
+        message: String,
+        source: DbErr,
+    },
+    /// The host name could not be resolved.
+    #[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+    #[error("Host name resolution failed: {message}")]
+    DnsResolution { message: String, source: DbErr },
+    /// The server is reachable but has no database by that name (or, for
+    /// SQLite, the file cannot be opened).
+    #[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+    #[error("Database does not exist: {message}")]
+    DatabaseDoesNotExist { message: String, source: DbErr },
 }
 
-impl fmt::Display for ConnectionError {
+/// A [`DbConnector`](crate::DbConnector) setting, as reported by
+/// [`ConnectionError::MissingField`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ConfigField {
+    DbType,
+    Host,
+    Username,
+    Password,
+    /// The database name, or for SQLite the file path.
+    Database,
+}
+
+impl ConfigField {
+    /// The field as named in messages, e.g. `"database type"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ConfigField::DbType => "database type",
+            ConfigField::Host => "host",
+            ConfigField::Username => "username",
+            ConfigField::Password => "password",
+            ConfigField::Database => "database",
+        }
+    }
+}
+
+impl fmt::Display for ConfigField {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A stable identifier for each kind of [`ConnectionError`], the same
+/// whichever backends are compiled in.
+///
+/// New codes may be added, but existing ones keep their meaning and their
+/// [`as_str()`](Self::as_str) value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorCode {
+    InvalidConfig,
+    MissingField,
+    ConnectionFailed,
+    DatabaseError,
+    QueryTimeout,
+    PoolClosed,
+    AuthenticationFailed,
+    TlsHandshakeFailed,
+    ConnectTimeout,
+    DnsResolution,
+    DatabaseDoesNotExist,
+}
+
+impl ErrorCode {
+    /// The code in snake case, e.g. `"authentication_failed"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::InvalidConfig => "invalid_config",
+            ErrorCode::MissingField => "missing_field",
+            ErrorCode::ConnectionFailed => "connection_failed",
+            ErrorCode::DatabaseError => "database_error",
+            ErrorCode::QueryTimeout => "query_timeout",
+            ErrorCode::PoolClosed => "pool_closed",
+            ErrorCode::AuthenticationFailed => "authentication_failed",
+            ErrorCode::TlsHandshakeFailed => "tls_handshake_failed",
+            ErrorCode::ConnectTimeout => "connect_timeout",
+            ErrorCode::DnsResolution => "dns_resolution",
+            ErrorCode::DatabaseDoesNotExist => "database_does_not_exist",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl ConnectionError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ConnectionError::InvalidConfig(_) => ErrorCode::InvalidConfig,
+            ConnectionError::MissingField(_) => ErrorCode::MissingField,
+            ConnectionError::ConnectionFailed(_) => ErrorCode::ConnectionFailed,
+            ConnectionError::DatabaseError(_) => ErrorCode::DatabaseError,
+            ConnectionError::QueryTimeout(_) => ErrorCode::QueryTimeout,
+            ConnectionError::PoolClosed => ErrorCode::PoolClosed,
+            #[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+            ConnectionError::AuthenticationFailed { .. } => ErrorCode::AuthenticationFailed,
+            #[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+            ConnectionError::TlsHandshakeFailed { .. } => ErrorCode::TlsHandshakeFailed,
+            #[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+            ConnectionError::Timeout { .. } => ErrorCode::ConnectTimeout,
+            #[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+            ConnectionError::DnsResolution { .. } => ErrorCode::DnsResolution,
+            #[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+            ConnectionError::DatabaseDoesNotExist { .. } => ErrorCode::DatabaseDoesNotExist,
+        }
+    }
+
+    /// Whether the failure is worth retrying, and why. Errors reported as
+    /// [`DatabaseError`](Self::DatabaseError) carry only a message and count
+    /// as permanent; use [`classify`](crate::classify) on the `DbErr` itself
+    /// to tell those apart.
+    #[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+    pub fn class(&self) -> crate::ErrorClass {
+        use crate::ErrorClass;
         match self {
-            ConnectionError::InvalidConfig(msg) => write!(f, "Configuration error: {msg}"),
-            ConnectionError::ConnectionFailed(msg) => write!(f, "Connection failed: {msg}"),
-            ConnectionError::DatabaseError(msg) => write!(f, "Database error: {msg}"),
+            ConnectionError::ConnectionFailed(_)
+            | ConnectionError::QueryTimeout(_)
+            | ConnectionError::Timeout { .. }
+            | ConnectionError::DnsResolution { .. } => ErrorClass::Transient,
+            ConnectionError::InvalidConfig(_)
+            | ConnectionError::MissingField(_)
+            | ConnectionError::DatabaseError(_)
+            | ConnectionError::PoolClosed
+            | ConnectionError::AuthenticationFailed { .. }
+            | ConnectionError::TlsHandshakeFailed { .. }
+            | ConnectionError::DatabaseDoesNotExist { .. } => ErrorClass::Permanent,
+        }
+    }
+
+    /// Whether trying again may succeed. See [`class`](Self::class).
+    #[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+    pub fn is_retryable(&self) -> bool {
+        self.class().is_retryable()
+    }
+
+    /// Whether the failure came from a temporary condition of the server or
+    /// network, such as a timeout or an unreachable host.
+    #[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+    pub fn is_transient(&self) -> bool {
+        self.class().is_transient()
+    }
+
+    /// Prefixes the message with `context`, e.g. the name of the connection.
+    #[cfg_attr(
+        not(any(feature = "postgres", feature = "mysql", feature = "sqlite")),
+        allow(dead_code)
+    )]
+    pub(crate) fn context(mut self, context: &str) -> Self {
+        match &mut self {
+            ConnectionError::InvalidConfig(msg)
+            | ConnectionError::ConnectionFailed(msg)
+            | ConnectionError::DatabaseError(msg) => *msg = format!("{context}: {msg}"),
+            ConnectionError::MissingField(_)
+            | ConnectionError::QueryTimeout(_)
+            | ConnectionError::PoolClosed => {}
+            #[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+            ConnectionError::AuthenticationFailed { message, .. }
+            | ConnectionError::TlsHandshakeFailed { message, .. }
+            | ConnectionError::Timeout { message, .. }
+            | ConnectionError::DnsResolution { message, .. }
+            | ConnectionError::DatabaseDoesNotExist { message, .. } => {
+                *message = format!("{context}: {message}")
+            }
+        }
+        self
+    }
+
+    /// Whether the failure is specific to the host tried, so another host
+    /// may well succeed.
+    #[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+    pub(crate) fn is_unreachable(&self) -> bool {
+        matches!(
+            self,
+            ConnectionError::ConnectionFailed(_)
+                | ConnectionError::Timeout { .. }
+                | ConnectionError::DnsResolution { .. }
+        )
+    }
+
+    /// Sorts an error from opening a pool into the variant describing it.
+    /// `elapsed` is how long the attempt took, and `secret` is masked out of
+    /// the message.
+    #[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+    pub(crate) fn from_connect(err: DbErr, elapsed: Duration, secret: Option<&str>) -> Self {
+        let message = crate::redact::mask_secret(&err.to_string(), secret);
+        let sqlx_err = match &err {
+            DbErr::ConnectionAcquire(ConnAcquireErr::Timeout) => {
+                return ConnectionError::Timeout {
+                    elapsed,
+                    message,
+                    source: err,
+                };
+            }
+            DbErr::Conn(RuntimeErr::SqlxError(e)) => e,
+            _ => return ConnectionError::ConnectionFailed(message),
+        };
+        match sqlx_err {
+            sqlx::Error::Configuration(_) => ConnectionError::InvalidConfig(message),
+            sqlx::Error::PoolTimedOut => ConnectionError::Timeout {
+                elapsed,
+                message,
+                source: err,
+            },
+            sqlx::Error::Tls(_) => ConnectionError::TlsHandshakeFailed {
+                message,
+                source: err,
+            },
+            // std reports getaddrinfo failures as an uncategorized I/O error.
+            sqlx::Error::Io(e) if e.to_string().contains("failed to lookup address") => {
+                ConnectionError::DnsResolution {
+                    message,
+                    source: err,
+                }
+            }
+            sqlx::Error::Database(db) => match database_error_kind(db.as_ref()) {
+                Some(DatabaseErrorKind::Authentication) => ConnectionError::AuthenticationFailed {
+                    message,
+                    source: err,
+                },
+                Some(DatabaseErrorKind::UnknownDatabase) => ConnectionError::DatabaseDoesNotExist {
+                    message,
+                    source: err,
+                },
+                // Server errors such as too_many_connections pass; anything
+                // else will fail the same way next time.
+                None if crate::classify(&err).is_retryable() => {
+                    ConnectionError::ConnectionFailed(message)
+                }
+                None => ConnectionError::DatabaseError(message),
+            },
+            _ => ConnectionError::ConnectionFailed(message),
         }
     }
 }
 
-impl std::error::Error for ConnectionError {}
+/// Lets closures returning `Result<_, DbErr>` surface a
+/// [`ConnectionError`], e.g. from
+/// [`ShardedConnector::for_each_shard`](crate::ShardedConnector::for_each_shard).
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+impl From<ConnectionError> for DbErr {
+    fn from(e: ConnectionError) -> Self {
+        DbErr::Custom(e.to_string())
+    }
+}
+
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+enum DatabaseErrorKind {
+    Authentication,
+    UnknownDatabase,
+}
+
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+fn database_error_kind(db: &dyn sqlx::error::DatabaseError) -> Option<DatabaseErrorKind> {
+    #[cfg(feature = "mysql")]
+    if let Some(mysql) = db.try_downcast_ref::<sqlx::mysql::MySqlDatabaseError>() {
+        // ER_ACCESS_DENIED_ERROR, ER_BAD_DB_ERROR
+        return match mysql.number() {
+            1045 => Some(DatabaseErrorKind::Authentication),
+            1049 => Some(DatabaseErrorKind::UnknownDatabase),
+            _ => None,
+        };
+    }
+    #[cfg(feature = "sqlite")]
+    if db.try_downcast_ref::<sqlx::sqlite::SqliteError>().is_some() {
+        // SQLITE_CANTOPEN
+        return (db.code().as_deref() == Some("14")).then_some(DatabaseErrorKind::UnknownDatabase);
+    }
+    // Postgres: invalid_authorization_specification, invalid_password,
+    // invalid_catalog_name
+    match db.code().as_deref() {
+        Some("28000" | "28P01") => Some(DatabaseErrorKind::Authentication),
+        Some("3D000") => Some(DatabaseErrorKind::UnknownDatabase),
+        _ => None,
+    }
+}
+
+#[cfg(all(test, any(feature = "postgres", feature = "mysql", feature = "sqlite")))]
+mod tests {
+    use super::*;
+    use crate::ErrorClass;
+    use std::borrow::Cow;
+
+    #[derive(Debug)]
+    struct ServerError(&'static str);
+
+    impl fmt::Display for ServerError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "server error {}", self.0)
+        }
+    }
+
+    impl std::error::Error for ServerError {}
+
+    impl sqlx::error::DatabaseError for ServerError {
+        fn message(&self) -> &str {
+            "server error"
+        }
+
+        fn code(&self) -> Option<Cow<'_, str>> {
+            Some(Cow::Borrowed(self.0))
+        }
+
+        fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+            self
+        }
+
+        fn kind(&self) -> sqlx::error::ErrorKind {
+            sqlx::error::ErrorKind::Other
+        }
+    }
+
+    fn connect_error(e: sqlx::Error) -> ConnectionError {
+        ConnectionError::from_connect(DbErr::Conn(RuntimeErr::SqlxError(e)), Duration::ZERO, None)
+    }
+
+    #[test]
+    fn bad_configuration_is_permanent() {
+        let e = connect_error(sqlx::Error::Configuration("unknown sslmode".into()));
+        assert_eq!(e.code(), ErrorCode::InvalidConfig);
+        assert_eq!(e.class(), ErrorClass::Permanent);
+    }
+
+    #[test]
+    fn server_errors_are_classed_by_code() {
+        // too_many_connections
+        let e = connect_error(sqlx::Error::Database(Box::new(ServerError("53300"))));
+        assert_eq!(e.code(), ErrorCode::ConnectionFailed);
+        assert!(e.is_transient());
+
+        // insufficient_privilege
+        let e = connect_error(sqlx::Error::Database(Box::new(ServerError("42501"))));
+        assert_eq!(e.code(), ErrorCode::DatabaseError);
+        assert_eq!(e.class(), ErrorClass::Permanent);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn closed_pool_is_permanent() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let conn = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+            let db = crate::TinSeaConn::new(conn);
+            db.clone().close().await.unwrap();
+            let e = db.acquire().unwrap_err();
+            assert_eq!(e.code(), ErrorCode::PoolClosed);
+            assert!(!e.is_retryable());
+        });
+    }
+}
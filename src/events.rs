@@ -0,0 +1,90 @@
+use crate::DatabaseType;
+use std::fmt;
+use std::sync::Arc;
+
+/// The connection an [`EventListener`] callback is about.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct ConnectionEvent<'a> {
+    pub db_type: Option<DatabaseType>,
+    /// The host connected to, when known.
+    pub host: Option<&'a str>,
+}
+
+/// Callbacks for the life of a connection, registered with
+/// [`DbConnector::event_listener`], e.g. to flip a feature flag, raise an
+/// alert or degrade gracefully while the database is away.
+///
+/// Every method does nothing by default. They are called inline, so
+/// anything slow should be handed off.
+pub trait EventListener: Send + Sync + 'static {
+    /// A pool was opened by `connect()` or one of the wrappers built on it.
+    fn on_connected(&self, event: &ConnectionEvent<'_>) {
+        let _ = event;
+    }
+
+    /// The database stopped answering: a
+    /// [`SupervisedConnection`](crate::SupervisedConnection) gave up on its
+    /// pool, or a [`TinSeaConn::ping`](crate::TinSeaConn::ping) failed after
+    /// succeeding.
+    fn on_connection_lost(&self, event: &ConnectionEvent<'_>, error: &str) {
+        let _ = (event, error);
+    }
+
+    /// The database answers again after
+    /// [`on_connection_lost`](Self::on_connection_lost).
+    fn on_reconnected(&self, event: &ConnectionEvent<'_>) {
+        let _ = event;
+    }
+
+    /// The pool was closed on purpose, through
+    /// [`TinSeaConn::close`](crate::TinSeaConn::close) or by closing the
+    /// pool of a [`SupervisedConnection`](crate::SupervisedConnection).
+    fn on_pool_closed(&self, event: &ConnectionEvent<'_>) {
+        let _ = event;
+    }
+}
+
+/// The listeners registered on a connector, with its database type, kept
+/// by whatever fires events after `connect()` has consumed the connector.
+#[derive(Clone, Default)]
+pub(crate) struct Listeners {
+    db_type: Option<DatabaseType>,
+    listeners: Arc<[Arc<dyn EventListener>]>,
+}
+
+#[cfg_attr(
+    not(any(feature = "postgres", feature = "mysql", feature = "sqlite")),
+    allow(dead_code)
+)]
+impl Listeners {
+    pub(crate) fn new(db_type: Option<DatabaseType>, listeners: &[Arc<dyn EventListener>]) -> Self {
+        Self {
+            db_type,
+            listeners: listeners.into(),
+        }
+    }
+
+    /// Calls `f` on every listener, about a connection to `host`.
+    pub(crate) fn notify(
+        &self,
+        host: Option<&str>,
+        f: impl Fn(&dyn EventListener, &ConnectionEvent<'_>),
+    ) {
+        let event = ConnectionEvent {
+            db_type: self.db_type,
+            host,
+        };
+        for listener in self.listeners.iter() {
+            f(listener.as_ref(), &event);
+        }
+    }
+}
+
+impl fmt::Debug for Listeners {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Listeners")
+            .field("count", &self.listeners.len())
+            .finish()
+    }
+}
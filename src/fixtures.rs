@@ -0,0 +1,162 @@
+//! Loading test data from CSV and JSON files into tables.
+
+use crate::ConnectionError;
+use sea_orm::sea_query::{
+    Alias, InsertStatement, MysqlQueryBuilder, PostgresQueryBuilder, Query, SimpleExpr,
+    SqliteQueryBuilder, Value,
+};
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, TransactionTrait};
+use std::path::Path;
+
+/// Rows per `INSERT`, keeping statements well below the packet and
+/// statement size limits of each backend.
+const ROWS_PER_INSERT: usize = 500;
+
+/// A fixture file read into memory.
+struct Rows {
+    columns: Vec<String>,
+    rows: Vec<Vec<Value>>,
+}
+
+fn read_failed(path: &Path, e: impl std::fmt::Display) -> ConnectionError {
+    ConnectionError::InvalidConfig(format!("Failed to read fixture {}: {e}", path.display()))
+}
+
+/// A CSV file with a header row naming the columns. Empty fields are NULL.
+fn read_csv(path: &Path) -> Result<Rows, ConnectionError> {
+    let mut reader = csv::Reader::from_path(path).map_err(|e| read_failed(path, e))?;
+    let columns = reader
+        .headers()
+        .map_err(|e| read_failed(path, e))?
+        .iter()
+        .map(str::to_string)
+        .collect();
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| read_failed(path, e))?;
+        rows.push(
+            record
+                .iter()
+                .map(|field| match field {
+                    "" => Value::String(None),
+                    field => Value::from(field),
+                })
+                .collect(),
+        );
+    }
+    Ok(Rows { columns, rows })
+}
+
+/// A JSON array of objects, one per row. The columns are every key seen,
+/// in order of first appearance; keys an object lacks are NULL.
+fn read_json(path: &Path) -> Result<Rows, ConnectionError> {
+    let content = std::fs::read_to_string(path).map_err(|e| read_failed(path, e))?;
+    let objects: Vec<serde_json::Map<String, serde_json::Value>> =
+        serde_json::from_str(&content).map_err(|e| read_failed(path, e))?;
+    let mut columns: Vec<String> = Vec::new();
+    for key in objects.iter().flat_map(|object| object.keys()) {
+        if !columns.contains(key) {
+            columns.push(key.clone());
+        }
+    }
+    let rows = objects
+        .iter()
+        .map(|object| {
+            columns
+                .iter()
+                .map(|column| object.get(column).map_or(Value::String(None), json_value))
+                .collect()
+        })
+        .collect();
+    Ok(Rows { columns, rows })
+}
+
+/// Nested arrays and objects are inserted as JSON text.
+fn json_value(value: &serde_json::Value) -> Value {
+    match value {
+        serde_json::Value::Null => Value::String(None),
+        serde_json::Value::Bool(b) => Value::from(*b),
+        serde_json::Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => Value::from(i),
+            (None, Some(u)) => Value::from(u),
+            _ => Value::from(n.as_f64()),
+        },
+        serde_json::Value::String(s) => Value::from(s.as_str()),
+        nested => Value::from(nested.to_string()),
+    }
+}
+
+/// Inserts the rows of a CSV or JSON file into `table` and returns how
+/// many were inserted. The format follows the file extension:
+///
+/// - `.csv`: a header row naming the columns, then one record per row;
+///   empty fields are NULL.
+/// - `.json`: an array of objects, one per row, keyed by column; missing
+///   keys are NULL and nested arrays or objects are inserted as JSON text.
+///
+/// `table` may be schema-qualified (`audit.events`). Values are sent as SQL
+/// literals, so the database converts them to the column types as it would
+/// for hand-written inserts (`'42'` into an integer column). Rows are
+/// inserted in batches of multi-row `INSERT`s inside one transaction, so a
+/// file is loaded completely or not at all.
+pub async fn load_fixture<P: AsRef<Path>>(
+    conn: &DatabaseConnection,
+    table: &str,
+    path: P,
+) -> Result<u64, ConnectionError> {
+    let path = path.as_ref();
+    let Rows { columns, rows } = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("csv") => read_csv(path)?,
+        Some("json") => read_json(path)?,
+        _ => {
+            return Err(ConnectionError::InvalidConfig(format!(
+                "Fixture {} is neither .csv nor .json",
+                path.display()
+            )));
+        }
+    };
+    if rows.is_empty() {
+        return Ok(0);
+    }
+
+    let backend = conn.get_database_backend();
+    let failed = |e: &dyn std::fmt::Display| {
+        ConnectionError::DatabaseError(format!(
+            "Loading fixture {} into {table} failed: {e}",
+            path.display()
+        ))
+    };
+    let txn = conn.begin().await.map_err(|e| failed(&e))?;
+    for chunk in rows.chunks(ROWS_PER_INSERT) {
+        let mut insert = Query::insert();
+        match table.split_once('.') {
+            Some((schema, name)) => insert.into_table((Alias::new(schema), Alias::new(name))),
+            None => insert.into_table(Alias::new(table)),
+        };
+        insert.columns(columns.iter().map(Alias::new));
+        for row in chunk {
+            insert
+                .values(row.iter().cloned().map(SimpleExpr::from))
+                .map_err(|e| failed(&e))?;
+        }
+        txn.execute_unprepared(&render(backend, &insert))
+            .await
+            .map_err(|e| failed(&e))?;
+    }
+    txn.commit().await.map_err(|e| failed(&e))?;
+    log::info!(
+        "Loaded {} row(s) into {table} from {}",
+        rows.len(),
+        path.display()
+    );
+    Ok(rows.len() as u64)
+}
+
+/// The statement as SQL with its values inlined as literals.
+fn render(backend: DbBackend, insert: &InsertStatement) -> String {
+    match backend {
+        DbBackend::Postgres => insert.to_string(PostgresQueryBuilder),
+        DbBackend::MySql => insert.to_string(MysqlQueryBuilder),
+        DbBackend::Sqlite => insert.to_string(SqliteQueryBuilder),
+    }
+}
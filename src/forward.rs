@@ -0,0 +1,163 @@
+//! Local relay that the pool connects to instead of the database, for when
+//! each upstream connection needs setting up in a way sqlx cannot do itself:
+//! through a SOCKS5 proxy, or with TCP keepalive and user timeout options.
+
+use crate::ConnectionError;
+use sea_orm::DatabaseConnection;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::AbortHandle;
+
+/// How often the forwarder checks whether its pool has been closed.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// TCP options for the sockets towards the database.
+#[cfg(feature = "tcp-keepalive")]
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct TcpOptions {
+    pub(crate) keepalive: Option<Duration>,
+    pub(crate) user_timeout: Option<Duration>,
+}
+
+#[cfg(feature = "tcp-keepalive")]
+impl TcpOptions {
+    pub(crate) fn is_set(&self) -> bool {
+        self.keepalive.is_some() || self.user_timeout.is_some()
+    }
+
+    fn apply(&self, stream: &TcpStream) -> std::io::Result<()> {
+        let socket = socket2::SockRef::from(stream);
+        if let Some(idle) = self.keepalive {
+            let keepalive = socket2::TcpKeepalive::new().with_time(idle);
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "android",
+                target_os = "macos",
+                target_os = "windows"
+            ))]
+            let keepalive = keepalive.with_interval((idle / 3).max(Duration::from_secs(1)));
+            socket.set_tcp_keepalive(&keepalive)?;
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if let Some(timeout) = self.user_timeout {
+            socket.set_tcp_user_timeout(Some(timeout))?;
+        }
+        Ok(())
+    }
+}
+
+/// Where and how the relay connects for each pool connection.
+#[derive(Debug, Clone)]
+pub(crate) struct Upstream {
+    pub(crate) host: String,
+    pub(crate) port: u16,
+    #[cfg(feature = "socks5")]
+    pub(crate) proxy: Option<crate::socks5::Socks5Proxy>,
+    #[cfg(feature = "tcp-keepalive")]
+    pub(crate) tcp: TcpOptions,
+}
+
+impl Upstream {
+    async fn connect(&self) -> Result<TcpStream, ConnectionError> {
+        #[cfg(feature = "socks5")]
+        if let Some(proxy) = &self.proxy {
+            let stream = proxy.connect(&self.host, self.port).await?;
+            return self.configure(stream);
+        }
+        let stream = TcpStream::connect((self.host.as_str(), self.port))
+            .await
+            .map_err(|e| {
+                ConnectionError::ConnectionFailed(format!(
+                    "Failed to connect to {}:{}: {e}",
+                    self.host, self.port
+                ))
+            })?;
+        self.configure(stream)
+    }
+
+    fn configure(&self, stream: TcpStream) -> Result<TcpStream, ConnectionError> {
+        #[cfg(feature = "tcp-keepalive")]
+        self.tcp.apply(&stream).map_err(|e| {
+            ConnectionError::ConnectionFailed(format!("Failed to set TCP options: {e}"))
+        })?;
+        Ok(stream)
+    }
+}
+
+/// A local listener relaying to the database. Dropped before
+/// [`attach`](Self::attach), it stops immediately.
+pub(crate) struct Forwarder {
+    pub(crate) local_port: u16,
+    pool: Arc<OnceLock<DatabaseConnection>>,
+    task: AbortHandle,
+    attached: bool,
+}
+
+impl Forwarder {
+    /// Ties the forwarder's lifetime to the pool: it stops once `conn` is
+    /// closed.
+    pub(crate) fn attach(mut self, conn: &DatabaseConnection) {
+        let _ = self.pool.set(conn.clone());
+        self.attached = true;
+    }
+}
+
+impl Drop for Forwarder {
+    fn drop(&mut self) {
+        if !self.attached {
+            self.task.abort();
+        }
+    }
+}
+
+/// Checks that `upstream` is reachable, then starts a local listener relaying
+/// each accepted connection to it.
+pub(crate) async fn start(upstream: Upstream) -> Result<Forwarder, ConnectionError> {
+    // Fail with a useful message now rather than with a reset connection
+    // from inside the pool.
+    drop(upstream.connect().await?);
+
+    let bind_error =
+        |e| ConnectionError::ConnectionFailed(format!("Failed to bind local forwarder: {e}"));
+    let listener = TcpListener::bind(("127.0.0.1", 0))
+        .await
+        .map_err(bind_error)?;
+    let local_port = listener.local_addr().map_err(bind_error)?.port();
+
+    let pool = Arc::new(OnceLock::<DatabaseConnection>::new());
+    let upstream = Arc::new(upstream);
+    let task = crate::tasks::spawn("tin-sea-conn::forwarder", {
+        let pool = pool.clone();
+        async move {
+            loop {
+                tokio::select! {
+                    accepted = listener.accept() => {
+                        let Ok((mut local, _)) = accepted else { continue };
+                        let upstream = upstream.clone();
+                        tokio::spawn(async move {
+                            match upstream.connect().await {
+                                Ok(mut remote) => {
+                                    let _ = tokio::io::copy_bidirectional(&mut local, &mut remote).await;
+                                }
+                                Err(e) => log::warn!("{e}"),
+                            }
+                        });
+                    }
+                    _ = tokio::time::sleep(POLL_INTERVAL) => {
+                        if pool.get().is_some_and(crate::managed::is_closed) {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    });
+
+    Ok(Forwarder {
+        local_port,
+        pool,
+        task,
+        attached: false,
+    })
+}
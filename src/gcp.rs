@@ -0,0 +1,62 @@
+//! Google Cloud SQL unix-socket convention and IAM database authentication.
+
+use crate::ConnectionError;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Directory under which the Cloud SQL Auth Proxy and Cloud Run mount
+/// instance sockets.
+pub(crate) const DEFAULT_SOCKET_DIR: &str = "/cloudsql";
+
+/// Access tokens live for an hour; refresh well before that.
+pub(crate) const TOKEN_REFRESH_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+const METADATA_HOST: &str = "metadata.google.internal";
+const TOKEN_PATH: &str = "/computeMetadata/v1/instance/service-accounts/default/token";
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+/// Validates a `project:region:instance` connection name.
+pub(crate) fn validate_instance(instance: &str) -> Result<(), ConnectionError> {
+    let parts: Vec<&str> = instance.split(':').collect();
+    if parts.len() == 3 && parts.iter().all(|p| !p.is_empty()) {
+        Ok(())
+    } else {
+        Err(ConnectionError::InvalidConfig(format!(
+            "Invalid Cloud SQL instance '{instance}', expected project:region:instance"
+        )))
+    }
+}
+
+/// Fetches an OAuth2 access token for the attached service account, used as
+/// the password for Cloud SQL IAM database authentication.
+///
+/// `GOOGLE_OAUTH_ACCESS_TOKEN` takes precedence, which allows local
+/// development with `gcloud auth print-access-token`.
+pub(crate) async fn access_token() -> Result<String, ConnectionError> {
+    if let Ok(token) = std::env::var("GOOGLE_OAUTH_ACCESS_TOKEN")
+        && !token.is_empty()
+    {
+        return Ok(token);
+    }
+
+    let (status, body) = crate::http::get(
+        METADATA_HOST,
+        80,
+        TOKEN_PATH,
+        &[("Metadata-Flavor", "Google")],
+    )
+    .await?;
+    if status != 200 {
+        return Err(ConnectionError::ConnectionFailed(format!(
+            "GCP metadata server returned HTTP {status}"
+        )));
+    }
+    let response: TokenResponse = serde_json::from_str(&body).map_err(|e| {
+        ConnectionError::ConnectionFailed(format!("Invalid GCP token response: {e}"))
+    })?;
+    Ok(response.access_token)
+}
@@ -0,0 +1,354 @@
+//! Connection health: one-off checks for readiness probes, and tracking of
+//! health over time.
+
+use crate::PoolStats;
+#[cfg(feature = "http")]
+use crate::TinSeaConn;
+use sea_orm::DatabaseConnection;
+use std::fmt::Write;
+#[cfg(feature = "http")]
+use std::future::Future;
+use std::mem;
+#[cfg(feature = "http")]
+use std::pin::Pin;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::watch;
+use tokio::task::AbortHandle;
+
+/// Consecutive failed pings after which [`HealthMonitor`] reports `Down`.
+const MONITOR_DOWN_AFTER: u32 = 3;
+/// How long [`check`] waits for the ping.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The outcome of a [`check`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthReport {
+    /// The ping succeeded.
+    pub healthy: bool,
+    /// How long the ping took, or took to fail.
+    pub latency: Duration,
+    pub pool: PoolStats,
+    /// Why the ping failed.
+    pub last_error: Option<String>,
+}
+
+impl HealthReport {
+    /// 200 when healthy, 503 otherwise, for a readiness endpoint.
+    pub fn status_code(&self) -> u16 {
+        if self.healthy { 200 } else { 503 }
+    }
+
+    /// The report as a JSON object, for the body of a readiness endpoint:
+    ///
+    /// ```json
+    /// {"status":"up","latency_ms":0.84,"pool":{"size":2,"idle":2,"in_use":0,"max_connections":10},"last_error":null}
+    /// ```
+    pub fn to_json(&self) -> String {
+        let mut json = format!(
+            r#"{{"status":"{}","latency_ms":{:.2},"pool":{{"size":{},"idle":{},"in_use":{},"max_connections":{}}},"last_error":"#,
+            if self.healthy { "up" } else { "down" },
+            self.latency.as_secs_f64() * 1000.0,
+            self.pool.size,
+            self.pool.idle,
+            self.pool.in_use(),
+            self.pool.max_connections,
+        );
+        match &self.last_error {
+            Some(error) => write_json_string(&mut json, error),
+            None => json.push_str("null"),
+        }
+        json.push('}');
+        json
+    }
+
+    /// The report as an HTTP response: [`status_code()`](Self::status_code)
+    /// with [`to_json()`](Self::to_json) as the body. axum and hyper serve
+    /// it as is.
+    #[cfg(feature = "http")]
+    pub fn to_response(&self) -> ::http::Response<String> {
+        ::http::Response::builder()
+            .status(self.status_code())
+            .header(::http::header::CONTENT_TYPE, "application/json")
+            .header(::http::header::CACHE_CONTROL, "no-store")
+            .body(self.to_json())
+            .expect("a status code and static headers are always valid")
+    }
+}
+
+/// The future of a [`handler`] call.
+#[cfg(feature = "http")]
+pub type HandlerFuture = Pin<Box<dyn Future<Output = ::http::Response<String>> + Send>>;
+
+/// A readiness endpoint for `conn`: each call runs [`check`] on the pool
+/// currently in service and answers with
+/// [`HealthReport::to_response`]. It is an axum handler as is; in a hyper
+/// `service_fn`, await it and return the response.
+///
+/// ```ignore
+/// let app = Router::new().route("/ready", get(health::handler(db.clone())));
+/// ```
+///
+/// Takes a [`TinSeaConn`] or a plain [`DatabaseConnection`].
+#[cfg(feature = "http")]
+pub fn handler(
+    conn: impl Into<TinSeaConn>,
+) -> impl Fn() -> HandlerFuture + Clone + Send + Sync + 'static {
+    let conn = conn.into();
+    move || {
+        let conn = conn.connection();
+        Box::pin(async move { check(&conn).await.to_response() })
+    }
+}
+
+pub(crate) fn write_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Pings `conn`, giving up after 5 seconds, and reports the outcome
+/// together with the pool's stats.
+///
+/// Meant for a Kubernetes readiness probe. Serve
+/// [`status_code()`](HealthReport::status_code) and
+/// [`to_json()`](HealthReport::to_json) from the probe's endpoint, or, with
+/// the `http` feature, mount [`handler`] there.
+///
+/// A liveness probe should usually not depend on the database, so that an
+/// outage doesn't get every replica restarted.
+pub async fn check(conn: &DatabaseConnection) -> HealthReport {
+    // Sampled first, so the ping's own connection isn't counted.
+    let pool = PoolStats::of(conn);
+    let started = Instant::now();
+    let last_error = match tokio::time::timeout(CHECK_TIMEOUT, conn.ping()).await {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(_) => Some(format!(
+            "ping timed out after {}ms",
+            CHECK_TIMEOUT.as_millis()
+        )),
+    };
+    HealthReport {
+        healthy: last_error.is_none(),
+        latency: started.elapsed(),
+        pool,
+        last_error,
+    }
+}
+
+/// Connection health as seen by whoever records probe outcomes.
+///
+/// Timestamps mark when the current state was entered, so dashboards can show
+/// how long an outage has lasted rather than a bare up/down flag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HealthState {
+    /// No probe has succeeded yet. Failures here are init failures
+    /// (bad config, unreachable host) rather than outages.
+    NeverConnected,
+    Healthy {
+        since: SystemTime,
+    },
+    /// At least one probe failed after the connection had been healthy.
+    Degraded {
+        since: SystemTime,
+        last_error: String,
+    },
+    /// Enough consecutive probes failed to consider the database unavailable.
+    Down {
+        since: SystemTime,
+    },
+}
+
+impl HealthState {
+    pub fn is_healthy(&self) -> bool {
+        matches!(self, HealthState::Healthy { .. })
+    }
+
+    /// When the current state was entered, `None` for `NeverConnected`.
+    pub fn since(&self) -> Option<SystemTime> {
+        match self {
+            HealthState::NeverConnected => None,
+            HealthState::Healthy { since }
+            | HealthState::Degraded { since, .. }
+            | HealthState::Down { since } => Some(*since),
+        }
+    }
+}
+
+/// Folds probe outcomes into a [`HealthState`] and publishes every transition
+/// on a watch channel.
+#[derive(Debug)]
+pub struct HealthTracker {
+    tx: watch::Sender<HealthState>,
+    down_after: u32,
+    consecutive_failures: u32,
+}
+
+impl HealthTracker {
+    /// `down_after` is the number of consecutive failures after which a
+    /// degraded connection is reported as down.
+    pub fn new(down_after: u32) -> Self {
+        let (tx, _) = watch::channel(HealthState::NeverConnected);
+        Self {
+            tx,
+            down_after: down_after.max(1),
+            consecutive_failures: 0,
+        }
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<HealthState> {
+        self.tx.subscribe()
+    }
+
+    pub fn state(&self) -> HealthState {
+        self.tx.borrow().clone()
+    }
+
+    pub fn record_success(&mut self) {
+        self.consecutive_failures = 0;
+        self.tx.send_if_modified(|state| {
+            if state.is_healthy() {
+                return false;
+            }
+            *state = HealthState::Healthy {
+                since: SystemTime::now(),
+            };
+            true
+        });
+    }
+
+    pub fn record_failure<E: ToString>(&mut self, error: E) {
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        let failures = self.consecutive_failures;
+        let down_after = self.down_after;
+        let error = error.to_string();
+        self.tx.send_if_modified(|state| match state {
+            HealthState::NeverConnected | HealthState::Down { .. } => false,
+            HealthState::Healthy { .. } if failures < down_after => {
+                *state = HealthState::Degraded {
+                    since: SystemTime::now(),
+                    last_error: error,
+                };
+                true
+            }
+            HealthState::Degraded { last_error, .. } if failures < down_after => {
+                // Same state, but receivers still want the freshest error.
+                *last_error = error;
+                true
+            }
+            _ => {
+                *state = HealthState::Down {
+                    since: SystemTime::now(),
+                };
+                true
+            }
+        });
+    }
+}
+
+/// Background task that pings the database on an interval and reports
+/// health transitions.
+///
+/// Stops by itself once the pool is closed, or when [`stop`](Self::stop) is
+/// called. Dropping the monitor does not stop it.
+#[derive(Debug, Clone)]
+pub struct HealthMonitor {
+    state: watch::Receiver<HealthState>,
+    task: AbortHandle,
+}
+
+impl HealthMonitor {
+    /// Pings `conn` every `interval` and calls `on_change` each time the
+    /// state moves between `Healthy`, `Degraded` and `Down`. A ping that
+    /// takes longer than `interval` counts as failed; three failures in a
+    /// row mean `Down`.
+    pub fn spawn<F>(conn: DatabaseConnection, interval: Duration, mut on_change: F) -> Self
+    where
+        F: FnMut(&HealthState) + Send + 'static,
+    {
+        let mut tracker = HealthTracker::new(MONITOR_DOWN_AFTER);
+        let state = tracker.subscribe();
+        let task = crate::tasks::spawn("tin-sea-conn::health-monitor", async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                if crate::managed::is_closed(&conn) {
+                    return;
+                }
+                let before = tracker.state();
+                match tokio::time::timeout(interval, conn.ping()).await {
+                    Ok(Ok(())) => tracker.record_success(),
+                    Ok(Err(e)) => tracker.record_failure(e),
+                    Err(_) => tracker
+                        .record_failure(format!("ping timed out after {}ms", interval.as_millis())),
+                }
+                let after = tracker.state();
+                if mem::discriminant(&before) != mem::discriminant(&after) {
+                    on_change(&after);
+                }
+            }
+        });
+        Self { state, task }
+    }
+
+    pub fn state(&self) -> HealthState {
+        self.state.borrow().clone()
+    }
+
+    /// A receiver that sees every update, including a refreshed
+    /// `last_error` while degraded.
+    pub fn subscribe(&self) -> watch::Receiver<HealthState> {
+        self.state.clone()
+    }
+
+    pub fn stop(&self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(all(test, feature = "http"))]
+mod tests {
+    use super::{HealthReport, handler};
+    use crate::PoolStats;
+    use sea_orm::DatabaseConnection;
+    use std::time::Duration;
+
+    #[test]
+    fn responds_with_the_report() {
+        let report = HealthReport {
+            healthy: true,
+            latency: Duration::from_millis(2),
+            pool: PoolStats::default(),
+            last_error: None,
+        };
+        let response = report.to_response();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["content-type"], "application/json");
+        assert_eq!(response.body(), &report.to_json());
+    }
+
+    #[test]
+    fn handler_reports_an_unreachable_pool() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let ready = handler(DatabaseConnection::Disconnected);
+        let response = runtime.block_on(ready());
+        assert_eq!(response.status(), 503);
+        assert!(response.body().contains(r#""status":"down""#));
+    }
+}
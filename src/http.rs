@@ -0,0 +1,175 @@
+//! Minimal HTTP/1.0 client for metadata endpoints and secret stores.
+//!
+//! Cloud metadata services only speak plain HTTP on a local address, and the
+//! secret stores need nothing beyond a single request/response over TLS, so
+//! a full HTTP stack would be dead weight for the features that need them.
+
+use crate::ConnectionError;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Sends an HTTP/1.0 `GET` and returns the status code and body.
+#[cfg_attr(
+    not(all(
+        any(feature = "gcp", feature = "azure"),
+        any(feature = "postgres", feature = "mysql")
+    )),
+    allow(dead_code)
+)]
+pub(crate) async fn get(
+    host: &str,
+    port: u16,
+    path: &str,
+    headers: &[(&str, &str)],
+) -> Result<(u16, String), ConnectionError> {
+    send(false, host, port, "GET", path, headers, None, None).await
+}
+
+/// Sends `method` to an `http://` or `https://` URL and returns the status
+/// code and body. `ca_cert` is a PEM bundle trusted in addition to the
+/// bundled web PKI roots.
+#[cfg(any(feature = "vault", feature = "aws-secrets"))]
+pub(crate) async fn request(
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: Option<&str>,
+    ca_cert: Option<&std::path::Path>,
+) -> Result<(u16, String), ConnectionError> {
+    let invalid = || ConnectionError::InvalidConfig(format!("Invalid URL: {url}"));
+    let (tls, rest) = if let Some(rest) = url.strip_prefix("https://") {
+        (true, rest)
+    } else if let Some(rest) = url.strip_prefix("http://") {
+        (false, rest)
+    } else {
+        return Err(invalid());
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+        None => (authority, if tls { 443 } else { 80 }),
+    };
+    send(tls, host, port, method, path, headers, body, ca_cert).await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn send(
+    tls: bool,
+    host: &str,
+    port: u16,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: Option<&str>,
+    #[cfg_attr(
+        not(any(feature = "vault", feature = "aws-secrets")),
+        allow(unused_variables)
+    )]
+    ca_cert: Option<&std::path::Path>,
+) -> Result<(u16, String), ConnectionError> {
+    let err = |e: std::io::Error| {
+        ConnectionError::ConnectionFailed(format!("Request to {host}{path} failed: {e}"))
+    };
+
+    let mut req = format!("{method} {path} HTTP/1.0\r\nHost: {host}\r\nConnection: close\r\n");
+    for (name, value) in headers {
+        req.push_str(&format!("{name}: {value}\r\n"));
+    }
+    if let Some(body) = body {
+        req.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    req.push_str("\r\n");
+    req.push_str(body.unwrap_or_default());
+
+    let request = async {
+        let stream = TcpStream::connect((host, port)).await?;
+        if tls {
+            #[cfg(any(feature = "vault", feature = "aws-secrets"))]
+            {
+                let stream = tls_connect(host, stream, ca_cert).await?;
+                return exchange(stream, &req).await;
+            }
+            #[cfg(not(any(feature = "vault", feature = "aws-secrets")))]
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "TLS is not enabled",
+            ));
+        }
+        exchange(stream, &req).await
+    };
+    let response = tokio::time::timeout(TIMEOUT, request)
+        .await
+        .map_err(|_| {
+            ConnectionError::ConnectionFailed(format!("Request to {host}{path} timed out"))
+        })?
+        .map_err(err)?;
+
+    let response = String::from_utf8_lossy(&response);
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| {
+            ConnectionError::ConnectionFailed(format!("Malformed response from {host}{path}"))
+        })?;
+    Ok((status, body.to_string()))
+}
+
+async fn exchange<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    req: &str,
+) -> std::io::Result<Vec<u8>> {
+    stream.write_all(req.as_bytes()).await?;
+    let mut response = Vec::new();
+    match stream.read_to_end(&mut response).await {
+        // Plenty of servers drop the connection without a TLS close_notify;
+        // the response is complete regardless because HTTP/1.0 ends at EOF.
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof && !response.is_empty() => {}
+        other => {
+            other?;
+        }
+    }
+    Ok(response)
+}
+
+#[cfg(any(feature = "vault", feature = "aws-secrets"))]
+async fn tls_connect(
+    host: &str,
+    stream: TcpStream,
+    ca_cert: Option<&std::path::Path>,
+) -> std::io::Result<tokio_rustls::client::TlsStream<TcpStream>> {
+    use std::io::{Error, ErrorKind};
+    use std::sync::Arc;
+    use tokio_rustls::rustls::pki_types::pem::PemObject;
+    use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName};
+    use tokio_rustls::rustls::{ClientConfig, RootCertStore, crypto};
+
+    let mut roots = RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    if let Some(path) = ca_cert {
+        for cert in CertificateDer::pem_file_iter(path)
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?
+        {
+            let cert = cert.map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+            roots
+                .add(cert)
+                .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+        }
+    }
+    let config = ClientConfig::builder_with_provider(Arc::new(crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(Error::other)?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let name = ServerName::try_from(host.to_string())
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+    tokio_rustls::TlsConnector::from(Arc::new(config))
+        .connect(name, stream)
+        .await
+}
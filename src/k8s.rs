@@ -0,0 +1,208 @@
+//! Settings from a Kubernetes Secret or ConfigMap mounted as a directory,
+//! one file per key.
+
+use crate::env::read_secret_file;
+use crate::{ConnectionError, DbConnector};
+use std::collections::BTreeMap;
+use std::path::Path;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+use {sea_orm::DatabaseConnection, std::time::Duration, tokio::sync::watch};
+
+/// How often a watched directory is re-read.
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The files read, in the order they are applied. Where two name the same
+/// setting (`username`/`user`, `database`/`dbname`), the first wins.
+const KEYS: &[&str] = &[
+    "type", "host", "port", "username", "user", "password", "database", "dbname", "sslmode",
+    "ca.crt", "tls.crt", "tls.key",
+];
+
+/// The contents of every known key present in `dir`.
+fn read_keys(dir: &Path) -> Result<BTreeMap<&'static str, String>, ConnectionError> {
+    if !dir.is_dir() {
+        return Err(ConnectionError::InvalidConfig(format!(
+            "Secret directory {} does not exist",
+            dir.display()
+        )));
+    }
+    let mut keys = BTreeMap::new();
+    for &key in KEYS {
+        let path = dir.join(key);
+        if path.is_file() {
+            keys.insert(key, read_secret_file(&path)?);
+        }
+    }
+    Ok(keys)
+}
+
+fn connector_from_keys(
+    dir: &Path,
+    keys: &BTreeMap<&'static str, String>,
+) -> Result<DbConnector, ConnectionError> {
+    let get = |names: &[&str]| names.iter().find_map(|&name| keys.get(name));
+    let file = |key: &str| dir.join(key).display().to_string();
+    let mut connector = DbConnector::new();
+
+    if let Some(db_type) = get(&["type"]) {
+        connector = connector.db_type_name(db_type)?;
+    }
+    if let Some(host) = get(&["host"]) {
+        connector = connector.host(host);
+    }
+    if let Some(port) = get(&["port"]) {
+        connector = connector.port(port.parse().map_err(|_| {
+            ConnectionError::InvalidConfig(format!("Invalid port in {}: {port}", dir.display()))
+        })?);
+    }
+    if let Some(username) = get(&["username", "user"]) {
+        connector = connector.username(username);
+    }
+    if keys.contains_key("password") {
+        connector = connector.password_file(file("password"));
+    }
+    if let Some(database) = get(&["database", "dbname"]) {
+        connector = connector.database(database);
+    }
+    if let Some(mode) = get(&["sslmode"]) {
+        connector = connector.ssl_mode(mode.parse()?);
+    }
+    if keys.contains_key("ca.crt") {
+        connector = connector.ssl_root_cert(file("ca.crt"));
+    }
+    if keys.contains_key("tls.crt") && keys.contains_key("tls.key") {
+        connector = connector
+            .ssl_client_cert(file("tls.crt"))
+            .ssl_client_key(file("tls.key"));
+    }
+    Ok(connector)
+}
+
+impl DbConnector {
+    /// Build a connector from a Secret or ConfigMap mounted as a directory,
+    /// one file per key.
+    ///
+    /// Reads `type`, `host`, `port`, `username` (or `user`), `password`,
+    /// `database` (or `dbname`) and `sslmode`; missing keys and other files
+    /// are skipped, so a ConfigMap and a Secret can be mounted side by side
+    /// and combined with further builder calls. The `password` file is read
+    /// at connect time, like [`password_file`](Self::password_file). The
+    /// `ca.crt`, `tls.crt` and `tls.key` keys of a `kubernetes.io/tls`
+    /// Secret are used as the root and client certificates.
+    ///
+    /// Use [`watch_k8s_dir`] to follow the kubelet rotating the mount.
+    pub fn from_k8s_dir<P: AsRef<Path>>(path: P) -> Result<Self, ConnectionError> {
+        let dir = path.as_ref();
+        connector_from_keys(dir, &read_keys(dir)?)
+    }
+}
+
+/// A connection that follows updates to a mounted Secret or ConfigMap.
+///
+/// Created by [`watch_k8s_dir`]. Call [`connection()`](Self::connection) per
+/// unit of work rather than holding on to the returned handle, so work after
+/// a reload lands on the new pool.
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+#[derive(Debug, Clone)]
+pub struct SecretDirWatcher {
+    current: watch::Receiver<DatabaseConnection>,
+}
+
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+impl SecretDirWatcher {
+    /// The pool currently in service.
+    pub fn connection(&self) -> DatabaseConnection {
+        self.current.borrow().clone()
+    }
+
+    /// A receiver that is notified each time a reloaded pool is swapped in.
+    pub fn subscribe(&self) -> watch::Receiver<DatabaseConnection> {
+        self.current.clone()
+    }
+}
+
+/// Connects with the settings mounted at `path`, as read by
+/// [`DbConnector::from_k8s_dir`], then re-reads the directory every 5
+/// seconds.
+///
+/// `configure` is applied on top of the mounted settings each time, for the
+/// pool sizes, timeouts and other options the Secret does not carry. When
+/// the kubelet updates the mount (a rotated password or certificate, a new
+/// host), a new pool is opened with the new settings, swapped in, and the
+/// old one drained and closed. Settings that fail to read or connect are
+/// logged and the current pool stays in service; the same broken settings
+/// are not retried until the mount changes again. Watching stops when every
+/// handle is dropped or the current pool is closed.
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+pub async fn watch_k8s_dir<P, F>(path: P, configure: F) -> Result<SecretDirWatcher, ConnectionError>
+where
+    P: AsRef<Path>,
+    F: Fn(DbConnector) -> DbConnector + Send + 'static,
+{
+    let dir = path.as_ref().to_path_buf();
+    let mut keys = read_keys(&dir)?;
+    let conn = configure(connector_from_keys(&dir, &keys)?)
+        .connect()
+        .await?;
+    let (tx, current) = watch::channel(conn);
+
+    crate::tasks::spawn("tin-sea-conn::k8s-secret-watch", async move {
+        let mut rejected = None;
+        let mut last_error = String::new();
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            if tx.is_closed() || crate::managed::is_closed(&tx.borrow()) {
+                return;
+            }
+            let new_keys = match read_keys(&dir) {
+                Ok(new_keys) => new_keys,
+                Err(e) => {
+                    let message = e.to_string();
+                    if message != last_error {
+                        log::warn!("Keeping the current pool: {message}");
+                        last_error = message;
+                    }
+                    continue;
+                }
+            };
+            last_error.clear();
+            if new_keys == keys || rejected.as_ref() == Some(&new_keys) {
+                continue;
+            }
+
+            // Names only: the values include the password.
+            let changed: Vec<&str> = KEYS
+                .iter()
+                .copied()
+                .filter(|key| keys.get(key) != new_keys.get(key))
+                .collect();
+            log::info!(
+                "Mounted settings in {} changed ({}); reconnecting",
+                dir.display(),
+                changed.join(", ")
+            );
+            let result = match connector_from_keys(&dir, &new_keys) {
+                Ok(connector) => configure(connector).connect().await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(conn) => {
+                    rejected = None;
+                    keys = new_keys;
+                    let old = tx.send_replace(conn);
+                    log::info!("Swapped in a pool with reloaded settings");
+                    if let Err(e) = old.close().await {
+                        log::warn!("Failed to close the pool with old settings: {e}");
+                    }
+                }
+                Err(e) => {
+                    log::warn!("Keeping the current pool; reloaded settings failed: {e}");
+                    rejected = Some(new_keys);
+                }
+            }
+        }
+    });
+
+    Ok(SecretDirWatcher { current })
+}
@@ -0,0 +1,62 @@
+use crate::{ConnectionError, DbConnector};
+use std::time::{Duration, Instant};
+
+/// Round-trip times from [`DbConnector::measure_latency`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LatencyReport {
+    pub samples: u32,
+    /// How long opening the connection took, including TLS and
+    /// authentication.
+    pub connect: Duration,
+    pub min: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+}
+
+impl DbConnector {
+    /// Opens a single connection, pings the server `samples` times over it
+    /// and returns the round-trip times, then closes it. No pool is kept.
+    ///
+    /// Useful for picking the closest of several replicas or checking a new
+    /// region before committing a full pool to it. At least one ping is
+    /// made; the first failure is returned.
+    pub async fn measure_latency(&self, samples: u32) -> Result<LatencyReport, ConnectionError> {
+        let samples = samples.max(1);
+        let started = Instant::now();
+        let (conn, _) = self.single_connection().connect_any_host().await?;
+        let connect = started.elapsed();
+
+        let mut times = Vec::with_capacity(samples as usize);
+        let mut failure = None;
+        for _ in 0..samples {
+            let started = Instant::now();
+            if let Err(e) = conn.ping().await {
+                failure = Some(e);
+                break;
+            }
+            times.push(started.elapsed());
+        }
+        let _ = conn.close().await;
+        if let Some(e) = failure {
+            return Err(ConnectionError::DatabaseError(e.to_string()));
+        }
+
+        times.sort();
+        Ok(LatencyReport {
+            samples,
+            connect,
+            min: times[0],
+            p50: percentile(&times, 50),
+            p95: percentile(&times, 95),
+            max: times[times.len() - 1],
+        })
+    }
+}
+
+/// Nearest-rank percentile of sorted, non-empty `times`.
+fn percentile(times: &[Duration], percent: usize) -> Duration {
+    let rank = (times.len() * percent).div_ceil(100);
+    times[rank.saturating_sub(1)]
+}
@@ -1,5 +1,254 @@
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+mod acquire;
+#[cfg(all(
+    feature = "actix",
+    any(feature = "postgres", feature = "mysql", feature = "sqlite")
+))]
+mod actix_ext;
+#[cfg(feature = "postgres")]
+mod advisory_lock;
+#[cfg(all(
+    any(feature = "aws-iam", feature = "aws-secrets"),
+    any(feature = "postgres", feature = "mysql")
+))]
+mod aws;
+#[cfg(all(feature = "aws-secrets", any(feature = "postgres", feature = "mysql")))]
+mod aws_secrets;
+#[cfg(all(
+    feature = "axum",
+    any(feature = "postgres", feature = "mysql", feature = "sqlite")
+))]
+mod axum_ext;
+#[cfg(all(feature = "azure", any(feature = "postgres", feature = "mysql")))]
+mod azure;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+mod balance;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+mod bootstrap;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+mod classify;
+#[cfg(feature = "clap")]
+mod cli;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+mod cluster;
+#[cfg(feature = "config")]
+mod config;
 mod connection;
+mod credentials;
+mod database_url;
+mod effective;
+mod env;
 mod error;
+mod events;
+#[cfg(all(
+    feature = "fixtures",
+    any(feature = "postgres", feature = "mysql", feature = "sqlite")
+))]
+mod fixtures;
+#[cfg(all(
+    any(feature = "socks5", feature = "tcp-keepalive"),
+    any(feature = "postgres", feature = "mysql")
+))]
+mod forward;
+#[cfg(all(feature = "gcp", any(feature = "postgres", feature = "mysql")))]
+mod gcp;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+pub mod health;
+#[cfg(all(
+    any(
+        feature = "gcp",
+        feature = "azure",
+        feature = "vault",
+        feature = "aws-secrets"
+    ),
+    any(feature = "postgres", feature = "mysql")
+))]
+mod http;
+mod k8s;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+mod latency;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+mod managed;
+#[cfg(all(
+    feature = "migrations",
+    any(feature = "postgres", feature = "mysql", feature = "sqlite")
+))]
+mod migrate;
+#[cfg(feature = "mysql")]
+mod mycnf;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+mod observe;
+#[cfg(all(
+    feature = "otel",
+    any(feature = "postgres", feature = "mysql", feature = "sqlite")
+))]
+mod otel;
+#[cfg(feature = "postgres")]
+mod pgpass;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+mod pool;
+#[cfg(all(
+    feature = "metrics",
+    any(feature = "postgres", feature = "mysql", feature = "sqlite")
+))]
+mod pool_metrics;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+mod preflight;
+mod preset;
+mod redact;
+#[cfg(all(
+    any(
+        feature = "aws-iam",
+        feature = "gcp",
+        feature = "azure",
+        feature = "vault",
+        feature = "aws-secrets",
+        feature = "ssh-tunnel",
+        feature = "socks5",
+        feature = "tcp-keepalive"
+    ),
+    any(feature = "postgres", feature = "mysql")
+))]
+mod refresh;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+mod registry;
+#[cfg(all(
+    feature = "config",
+    any(feature = "postgres", feature = "mysql", feature = "sqlite")
+))]
+mod reload;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+mod retry;
+#[cfg(feature = "postgres")]
+mod rls;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+mod rotation;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+mod saturation;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+mod seed;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+mod self_test;
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+mod session;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+mod shard;
+#[cfg(all(feature = "socks5", any(feature = "postgres", feature = "mysql")))]
+mod socks5;
+#[cfg(feature = "sops")]
+mod sops;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(all(feature = "ssh-tunnel", any(feature = "postgres", feature = "mysql")))]
+mod ssh_tunnel;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+mod supervisor;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+mod tasks;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+mod tenant;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+mod timeout;
+#[cfg(all(
+    feature = "tracing",
+    any(feature = "postgres", feature = "mysql", feature = "sqlite")
+))]
+mod trace;
+#[cfg(all(feature = "vault", any(feature = "postgres", feature = "mysql")))]
+mod vault;
+#[cfg(all(
+    any(feature = "axum", feature = "actix"),
+    any(feature = "postgres", feature = "mysql", feature = "sqlite")
+))]
+mod web;
 
-pub use connection::{DatabaseType, DbConnector, SslMode};
-pub use error::ConnectionError;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+pub use acquire::{AcquiredConn, HeldConn, HoldStats};
+#[cfg(feature = "postgres")]
+pub use advisory_lock::AdvisoryLock;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+pub use balance::{LeastOutstanding, LoadBalancer, Random, RoundRobin, Weighted};
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+pub use bootstrap::reset_database;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+pub use classify::{ErrorClass, classify};
+#[cfg(feature = "clap")]
+pub use cli::DbConnectorArgs;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+pub use cluster::DbCluster;
+#[cfg(feature = "config")]
+pub use config::DbConfig;
+pub use connection::{
+    ChannelBinding, DatabaseType, DbConnector, GssEncMode, LogFormat, SslMode, TargetSessionAttrs,
+};
+pub use credentials::{CredentialProvider, Credentials};
+pub use effective::EffectiveConfig;
+pub use env::Platform;
+pub use error::{ConfigField, ConnectionError, ErrorCode};
+pub use events::{ConnectionEvent, EventListener};
+#[cfg(all(
+    feature = "fixtures",
+    any(feature = "postgres", feature = "mysql", feature = "sqlite")
+))]
+pub use fixtures::load_fixture;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+pub use health::{HealthMonitor, HealthReport, HealthState, HealthTracker};
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+pub use k8s::{SecretDirWatcher, watch_k8s_dir};
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+pub use latency::LatencyReport;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+pub use managed::{PoolHandoff, PoolStats, TinSeaConn};
+#[cfg(all(
+    feature = "migrations",
+    any(feature = "postgres", feature = "mysql", feature = "sqlite")
+))]
+pub use migrate::MigrationReport;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+pub use observe::{ObservedConnection, QueryEvent, QueryObserver, with_observer};
+#[cfg(all(
+    feature = "otel",
+    any(feature = "postgres", feature = "mysql", feature = "sqlite")
+))]
+pub use otel::OtelObserver;
+#[cfg(all(
+    feature = "metrics",
+    any(feature = "postgres", feature = "mysql", feature = "sqlite")
+))]
+pub use pool_metrics::MetricsObserver;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+pub use preflight::{PreflightFailure, PreflightReport, PreflightStage, PreflightStep};
+pub use preset::ConnectorPreset;
+pub use redact::redact_url;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+pub use registry::ConnectionRegistry;
+#[cfg(all(
+    feature = "config",
+    any(feature = "postgres", feature = "mysql", feature = "sqlite")
+))]
+pub use reload::{ConfigWatcher, watch_config};
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+pub use retry::{RetryPolicy, retry_transaction};
+#[cfg(feature = "postgres")]
+pub use rls::{TenantScope, with_tenant};
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+pub use rotation::RotatingCredentials;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+pub use shard::ShardedConnector;
+#[cfg(feature = "sops")]
+pub use sops::SopsKey;
+#[cfg(feature = "sqlite")]
+pub use sqlite::{SqliteHandle, TempSqliteDb};
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+pub use supervisor::SupervisedConnection;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+pub use tasks::{BackgroundTask, tasks};
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+pub use tenant::TenantConnectionManager;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlite"))]
+pub use timeout::{TimeoutConnection, with_timeout};
+#[cfg(all(
+    any(feature = "axum", feature = "actix"),
+    any(feature = "postgres", feature = "mysql", feature = "sqlite")
+))]
+pub use web::{Db, DbRejection};
@@ -0,0 +1,591 @@
+use crate::acquire::Holds;
+use crate::observe::RetirementCounter;
+use crate::{
+    AcquiredConn, ConnectionError, DbConnector, HeldConn, HoldStats, ObservedConnection,
+    QueryEvent, with_observer,
+};
+use sea_orm::{DatabaseConnection, DatabaseTransaction, DbErr};
+use std::future::Future;
+use std::panic::Location;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::watch;
+
+/// Snapshot of a connection pool's occupancy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Open connections, idle or in use.
+    pub size: u32,
+    pub idle: u32,
+    pub max_connections: u32,
+}
+
+impl PoolStats {
+    /// Occupancy of the pool behind `conn`; all zeros for a disconnected
+    /// handle.
+    pub fn of(conn: &DatabaseConnection) -> Self {
+        fn stats<DB: sea_orm::sqlx::Database>(pool: &sea_orm::sqlx::Pool<DB>) -> PoolStats {
+            PoolStats {
+                size: pool.size(),
+                idle: pool.num_idle() as u32,
+                max_connections: pool.options().get_max_connections(),
+            }
+        }
+        match conn {
+            #[cfg(feature = "postgres")]
+            DatabaseConnection::SqlxPostgresPoolConnection(_) => {
+                stats(conn.get_postgres_connection_pool())
+            }
+            #[cfg(feature = "mysql")]
+            DatabaseConnection::SqlxMySqlPoolConnection(_) => {
+                stats(conn.get_mysql_connection_pool())
+            }
+            #[cfg(feature = "sqlite")]
+            DatabaseConnection::SqlxSqlitePoolConnection(_) => {
+                stats(conn.get_sqlite_connection_pool())
+            }
+            _ => Self::default(),
+        }
+    }
+
+    pub fn in_use(&self) -> u32 {
+        self.size.saturating_sub(self.idle)
+    }
+
+    /// Whether every connection is open and in use, so callers have to
+    /// wait for one to be returned.
+    pub(crate) fn is_full(&self) -> bool {
+        self.idle == 0 && self.size >= self.max_connections
+    }
+}
+
+/// A connected pool with the lifecycle helpers services otherwise write
+/// around a bare [`DatabaseConnection`]. Clones share the pool and its
+/// health flag.
+///
+/// Call [`connection()`](Self::connection) per unit of work rather than
+/// holding on to the returned handle, so work after
+/// [`resize_pool`](Self::resize_pool) lands on the new pool.
+#[derive(Debug, Clone)]
+pub struct TinSeaConn {
+    current: Arc<watch::Sender<DatabaseConnection>>,
+    healthy: Arc<AtomicBool>,
+    /// Settings the pool was opened with, for rebuilding it.
+    connector: Option<Arc<Mutex<DbConnector>>>,
+    /// The host the current pool connected to, when opened from a connector.
+    host: Arc<Mutex<Option<String>>>,
+    listeners: crate::events::Listeners,
+    /// Statements and failures counted by [`observed`](Self::observed).
+    retirement: Option<Arc<RetirementCounter>>,
+    /// Guards handed out by [`acquire`](Self::acquire).
+    holds: Arc<Holds>,
+}
+
+impl TinSeaConn {
+    /// Wraps an already connected pool. Without the connector it came from,
+    /// [`resize_pool`](Self::resize_pool) is unavailable; use
+    /// [`DbConnector::connect_managed`] where possible.
+    pub fn new(conn: DatabaseConnection) -> Self {
+        Self {
+            current: Arc::new(watch::Sender::new(conn)),
+            healthy: Arc::new(AtomicBool::new(true)),
+            connector: None,
+            host: Arc::default(),
+            listeners: Default::default(),
+            retirement: None,
+            holds: Arc::default(),
+        }
+    }
+
+    /// The pool currently in service.
+    pub fn connection(&self) -> DatabaseConnection {
+        self.current.borrow().clone()
+    }
+
+    pub fn into_inner(self) -> DatabaseConnection {
+        self.connection()
+    }
+
+    /// The pool currently in service, as a guard to drop at the end of the
+    /// unit of work. Prefer it to [`connection()`](Self::connection): the
+    /// guard records how long it was held, is reported by
+    /// [`held`](Self::held) while it lives, and is waited for by
+    /// [`close_gracefully`](Self::close_gracefully).
+    ///
+    /// Does not wait, so it is safe to call in futures that may be
+    /// cancelled. Fails once the pool is closing or closed.
+    ///
+    /// ```ignore
+    /// let conn = db.acquire()?;
+    /// let users = User::find().all(&*conn).await?;
+    /// ```
+    #[track_caller]
+    pub fn acquire(&self) -> Result<AcquiredConn, ConnectionError> {
+        let conn = self.connection();
+        if self.holds.is_closing() || is_closed(&conn) {
+            return Err(ConnectionError::PoolClosed);
+        }
+        Ok(self.holds.acquire(conn, Location::caller()))
+    }
+
+    /// How long [`acquire`](Self::acquire) guards have been held.
+    pub fn hold_stats(&self) -> HoldStats {
+        self.holds.stats()
+    }
+
+    /// The [`acquire`](Self::acquire) guards still held, longest-held
+    /// first, with where each was acquired. A guard held far longer than
+    /// the work it was taken for is likely leaked.
+    pub fn held(&self) -> Vec<HeldConn> {
+        self.holds.held()
+    }
+
+    /// The pool currently in service, wrapped in an [`ObservedConnection`]
+    /// that counts its statements toward the connector's
+    /// [`max_uses_per_connection`](DbConnector::max_uses_per_connection)
+    /// and [`retire_after_errors`](DbConnector::retire_after_errors). Once
+    /// either limit is reached, the pool is replaced in the background as
+    /// by [`resize_pool`](Self::resize_pool) and the counts start over.
+    ///
+    /// Call it per unit of work, like [`connection()`](Self::connection).
+    /// Statements made through `connection()` are not counted.
+    pub fn observed(&self) -> ObservedConnection {
+        let conn = self.connection();
+        let Some(counter) = self.retirement.clone() else {
+            return with_observer(&conn, |_: &QueryEvent<'_>| {});
+        };
+        let managed = self.clone();
+        with_observer(&conn, move |event: &QueryEvent<'_>| {
+            if let Some(reason) = counter.record(event.error.is_some()) {
+                let managed = managed.clone();
+                tokio::spawn(async move { managed.retire(reason).await });
+            }
+        })
+    }
+
+    /// Replaces the pool once it has reached a retirement limit.
+    async fn retire(&self, reason: &str) {
+        let (Some(shared), Some(counter)) = (&self.connector, &self.retirement) else {
+            return;
+        };
+        if is_closed(&self.connection()) {
+            return;
+        }
+        let connector = shared.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        match self.replace_pool(shared, connector, "retirement").await {
+            Ok(()) => log::info!("Replaced the connection pool after reaching {reason}"),
+            Err(e) => {
+                // Try again once the limit is reached anew.
+                counter.reset();
+                log::warn!("Replacing the connection pool after reaching {reason} failed: {e}");
+            }
+        }
+    }
+
+    /// The host the current pool connected to, which with
+    /// [`DbConnector::hosts`] may be a fallback host. `None` for a pool
+    /// wrapped with [`new`](Self::new).
+    pub fn connected_host(&self) -> Option<String> {
+        self.host.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Changes the pool limits without a restart.
+    ///
+    /// sqlx pools have a fixed size, so this opens a new pool with the new
+    /// limits from the original connector (running the same post-connect
+    /// steps as `connect_managed()`), swaps it in, and then drains and
+    /// closes the old one in the background: checked-out connections finish
+    /// their work first. On error the current pool stays in service.
+    pub async fn resize_pool(&self, max: u32, min: u32) -> Result<(), ConnectionError> {
+        let lock = |connector: &Mutex<DbConnector>| {
+            connector.lock().unwrap_or_else(|e| e.into_inner()).clone()
+        };
+        let Some(shared) = &self.connector else {
+            return Err(ConnectionError::InvalidConfig(
+                "resize_pool needs a connection opened with connect_managed()".into(),
+            ));
+        };
+        let connector = lock(shared).max_connections(max).min_connections(min);
+        connector.validate()?;
+        self.replace_pool(shared, connector, "resize_pool").await?;
+        log::info!("Resized the connection pool to max {max}, min {min}");
+        Ok(())
+    }
+
+    /// Opens a pool with `connector`, swaps it in and drains and closes the
+    /// old one in the background. `replaced_by` names the caller in logs.
+    async fn replace_pool(
+        &self,
+        shared: &Mutex<DbConnector>,
+        connector: DbConnector,
+        replaced_by: &'static str,
+    ) -> Result<(), ConnectionError> {
+        let (conn, host) = connector.clone().connect_any_host().await?;
+        *shared.lock().unwrap_or_else(|e| e.into_inner()) = connector;
+        *self.host.lock().unwrap_or_else(|e| e.into_inner()) = host;
+        if let Some(counter) = &self.retirement {
+            counter.reset();
+        }
+
+        let old = self.current.send_replace(conn);
+        tokio::spawn(async move {
+            if let Err(e) = old.close().await {
+                log::warn!("Failed to close the pool replaced by {replaced_by}: {e}");
+            }
+        });
+        Ok(())
+    }
+
+    /// Captures the pool's settings and how many connections it has open,
+    /// so an in-process reload can re-establish the pool in a new manager
+    /// with [`PoolHandoff::resume`] instead of starting cold.
+    ///
+    /// Only configuration and intent are handed over, never the sockets
+    /// themselves. Needs a connection opened with
+    /// [`connect_managed()`](DbConnector::connect_managed).
+    pub fn handoff(&self) -> Result<PoolHandoff, ConnectionError> {
+        let Some(shared) = &self.connector else {
+            return Err(ConnectionError::InvalidConfig(
+                "handoff needs a connection opened with connect_managed()".into(),
+            ));
+        };
+        let connector = shared.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        Ok(PoolHandoff {
+            connector,
+            open: self.pool_stats().size,
+            previous: self.clone(),
+        })
+    }
+
+    /// Checks a connection out of the pool and round-trips to the server.
+    /// The outcome is what [`is_healthy`](Self::is_healthy) reports.
+    ///
+    /// A failure after a success, and a success after a failure, are
+    /// reported to the connector's [`EventListener`](crate::EventListener)s
+    /// as a lost connection and a reconnection.
+    pub async fn ping(&self) -> Result<(), ConnectionError> {
+        let result = self
+            .connection()
+            .ping()
+            .await
+            .map_err(|e| ConnectionError::DatabaseError(e.to_string()));
+        let was_healthy = self.healthy.swap(result.is_ok(), Ordering::Relaxed);
+        match &result {
+            Err(e) if was_healthy => {
+                self.notify(|l, event| l.on_connection_lost(event, &e.to_string()))
+            }
+            Ok(()) if !was_healthy => self.notify(|l, event| l.on_reconnected(event)),
+            _ => {}
+        }
+        result
+    }
+
+    fn notify(&self, f: impl Fn(&dyn crate::EventListener, &crate::ConnectionEvent<'_>)) {
+        let host = self.connected_host();
+        self.listeners.notify(host.as_deref(), f);
+    }
+
+    /// Runs `f` in a transaction with [`retry_transaction`](crate::retry_transaction),
+    /// using the [`retry_policy`](DbConnector::retry_policy) of the
+    /// connector the pool was opened with, or the default policy.
+    pub async fn retry_transaction<F, T>(&self, f: F) -> Result<T, DbErr>
+    where
+        F: for<'c> FnMut(
+            &'c DatabaseTransaction,
+        ) -> Pin<Box<dyn Future<Output = Result<T, DbErr>> + Send + 'c>>,
+    {
+        let policy = match &self.connector {
+            Some(connector) => connector
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .retry_policy_value(),
+            None => crate::RetryPolicy::default(),
+        };
+        crate::retry_transaction(&self.connection(), &policy, f).await
+    }
+
+    /// Whether the last [`ping`](Self::ping) succeeded (or, before any
+    /// ping, whether the pool connected) and the pool is still open. Does
+    /// not touch the network.
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed) && !is_closed(&self.connection())
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        PoolStats::of(&self.connection())
+    }
+
+    /// Waits for `signal`, then refuses new [`acquire`](Self::acquire)
+    /// guards while the ones already held are used up. Meant as a server's
+    /// graceful-shutdown future, so requests still arriving during the
+    /// shutdown fail fast; close the pool with
+    /// [`close_gracefully`](Self::close_gracefully) once the server has
+    /// stopped.
+    ///
+    /// ```ignore
+    /// axum::serve(listener, app)
+    ///     .with_graceful_shutdown(db.shutdown_on(shutdown_signal()))
+    ///     .await?;
+    /// db.close_gracefully(Instant::now() + Duration::from_secs(10)).await?;
+    /// ```
+    pub fn shutdown_on<F>(&self, signal: F) -> impl Future<Output = ()> + Send + 'static
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let holds = self.holds.clone();
+        async move {
+            signal.await;
+            holds.start_closing();
+        }
+    }
+
+    /// Closes the pool for every clone, waiting for checked-out
+    /// connections to be returned first.
+    pub async fn close(self) -> Result<(), ConnectionError> {
+        self.healthy.store(false, Ordering::Relaxed);
+        self.holds.start_closing();
+        let result = self
+            .connection()
+            .close()
+            .await
+            .map_err(|e| ConnectionError::DatabaseError(e.to_string()));
+        self.notify(|l, event| l.on_pool_closed(event));
+        result
+    }
+
+    /// Shuts the pool down for a clean termination: new checkouts fail
+    /// immediately, idle connections are closed, and connections still in
+    /// use are waited for until `deadline`.
+    ///
+    /// [`acquire`](Self::acquire) guards count as in use: new ones are
+    /// refused, and the pool is closed once those held are dropped.
+    ///
+    /// Returns how many connections were still checked out at the deadline.
+    /// The pool stops waiting for them; each is closed as soon as its holder
+    /// returns it rather than being reused. Where each guard still held was
+    /// acquired is logged.
+    pub async fn close_gracefully(self, deadline: Instant) -> Result<u32, ConnectionError> {
+        self.healthy.store(false, Ordering::Relaxed);
+        self.holds.start_closing();
+        let deadline = tokio::time::Instant::from_std(deadline);
+        let drained = tokio::time::timeout_at(deadline, self.holds.drained()).await;
+        // Polled at least once, so the pool is marked closed even when the
+        // deadline has already passed.
+        let closed = tokio::time::timeout_at(deadline, self.connection().close_by_ref()).await;
+        self.notify(|l, event| l.on_pool_closed(event));
+        match drained.and(closed) {
+            Ok(result) => result
+                .map(|_| 0)
+                .map_err(|e| ConnectionError::DatabaseError(e.to_string())),
+            Err(_) => {
+                let held = self.held();
+                for guard in &held {
+                    log::warn!(
+                        "Connection acquired at {} still held at the shutdown deadline, after {:?}",
+                        guard.location,
+                        guard.held_for
+                    );
+                }
+                let abandoned = self.pool_stats().in_use().max(held.len() as u32);
+                log::warn!(
+                    "{abandoned} database connection(s) still in use at the shutdown deadline"
+                );
+                Ok(abandoned)
+            }
+        }
+    }
+}
+
+impl From<DatabaseConnection> for TinSeaConn {
+    fn from(conn: DatabaseConnection) -> Self {
+        Self::new(conn)
+    }
+}
+
+/// A [`TinSeaConn`]'s settings and pool occupancy, taken by
+/// [`TinSeaConn::handoff`] and turned into a new manager by
+/// [`resume`](Self::resume).
+///
+/// ```ignore
+/// let next = db
+///     .handoff()?
+///     .map_connector(|connector| connector.max_connections(reloaded.max_connections))
+///     .resume()
+///     .await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct PoolHandoff {
+    connector: DbConnector,
+    /// Connections the previous pool had open.
+    open: u32,
+    previous: TinSeaConn,
+}
+
+impl PoolHandoff {
+    /// The settings the new pool will be opened with.
+    pub fn connector(&self) -> &DbConnector {
+        &self.connector
+    }
+
+    /// Changes the settings for the new pool, e.g. to apply reloaded
+    /// configuration.
+    pub fn map_connector(mut self, f: impl FnOnce(DbConnector) -> DbConnector) -> Self {
+        self.connector = f(self.connector);
+        self
+    }
+
+    /// Connections the previous pool had open when the hand-off was taken.
+    pub fn open_connections(&self) -> u32 {
+        self.open
+    }
+
+    /// Opens the new pool and warms it to as many connections as the
+    /// previous pool had open, so the reload does not show up as a burst
+    /// of new connections under load. The previous manager then stops
+    /// handing out connections, and its pool is drained and closed in the
+    /// background once the work still using it finishes.
+    ///
+    /// On error the previous pool stays in service. After success, switch
+    /// callers over to the returned manager; closing the previous one only
+    /// ever closes its own pool.
+    pub async fn resume(self) -> Result<TinSeaConn, ConnectionError> {
+        self.connector.validate()?;
+        let next = self.connector.connect_managed().await?;
+        let conn = next.connection();
+        if self.open > 0
+            && let Err(e) = warm_up(&conn, self.open).await
+        {
+            log::warn!("Warming up the handed-off pool failed: {e}");
+        }
+
+        log::info!(
+            "Handed off the connection pool ({} connection(s) open)",
+            self.open
+        );
+        let previous = self.previous;
+        tokio::spawn(async move {
+            if let Err(e) = previous.close().await {
+                log::warn!("Failed to close the pool replaced by a hand-off: {e}");
+            }
+        });
+        Ok(next)
+    }
+}
+
+/// Whether the pool behind `conn` has been closed.
+pub(crate) fn is_closed(conn: &DatabaseConnection) -> bool {
+    match conn {
+        #[cfg(feature = "postgres")]
+        DatabaseConnection::SqlxPostgresPoolConnection(_) => {
+            conn.get_postgres_connection_pool().is_closed()
+        }
+        #[cfg(feature = "mysql")]
+        DatabaseConnection::SqlxMySqlPoolConnection(_) => {
+            conn.get_mysql_connection_pool().is_closed()
+        }
+        #[cfg(feature = "sqlite")]
+        DatabaseConnection::SqlxSqlitePoolConnection(_) => {
+            conn.get_sqlite_connection_pool().is_closed()
+        }
+        _ => true,
+    }
+}
+
+/// Checks out `count` connections at once, so each is a distinct one,
+/// pinging each before returning them all to the pool.
+pub(crate) async fn warm_up(
+    conn: &DatabaseConnection,
+    count: u32,
+) -> Result<(), sea_orm::sqlx::Error> {
+    async fn warm<DB: sea_orm::sqlx::Database>(
+        pool: &sea_orm::sqlx::Pool<DB>,
+        count: u32,
+    ) -> Result<(), sea_orm::sqlx::Error> {
+        use sea_orm::sqlx::Connection;
+        let count = count.min(pool.options().get_max_connections());
+        let mut held = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let mut connection = pool.acquire().await?;
+            connection.ping().await?;
+            held.push(connection);
+        }
+        Ok(())
+    }
+    match conn {
+        #[cfg(feature = "postgres")]
+        DatabaseConnection::SqlxPostgresPoolConnection(_) => {
+            warm(conn.get_postgres_connection_pool(), count).await
+        }
+        #[cfg(feature = "mysql")]
+        DatabaseConnection::SqlxMySqlPoolConnection(_) => {
+            warm(conn.get_mysql_connection_pool(), count).await
+        }
+        #[cfg(feature = "sqlite")]
+        DatabaseConnection::SqlxSqlitePoolConnection(_) => {
+            warm(conn.get_sqlite_connection_pool(), count).await
+        }
+        _ => Ok(()),
+    }
+}
+
+impl DbConnector {
+    /// Like [`connect`](Self::connect), returning a [`TinSeaConn`].
+    pub async fn connect_managed(self) -> Result<TinSeaConn, ConnectionError> {
+        let connector = self.clone();
+        let listeners = self.listeners();
+        let (conn, host) = self.connect_any_host().await?;
+        listeners.notify(host.as_deref(), |l, e| l.on_connected(e));
+        let mut managed = TinSeaConn::new(conn);
+        #[cfg(feature = "otel")]
+        {
+            let target = match &host {
+                Some(host) => connector.clone().host(host.as_str()).otel_target(),
+                None => connector.otel_target(),
+            };
+            crate::otel::register_pool(&target, Arc::downgrade(&managed.current));
+        }
+        managed.retirement = RetirementCounter::new(
+            connector.max_uses_per_connection,
+            connector.retire_after_errors,
+        )
+        .map(Arc::new);
+        managed.holds = Arc::new(Holds::new(connector.leak_detection_threshold));
+        managed.connector = Some(Arc::new(Mutex::new(connector)));
+        managed.host = Arc::new(Mutex::new(host));
+        managed.listeners = listeners;
+        Ok(managed)
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use crate::DbConnector;
+    use sea_orm::ConnectionTrait;
+
+    #[test]
+    fn closing_the_handed_off_manager_keeps_the_new_pool() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let previous = DbConnector::from_url("sqlite::memory:")
+                .unwrap()
+                .connect_managed()
+                .await
+                .unwrap();
+            let next = previous.handoff().unwrap().resume().await.unwrap();
+
+            previous.clone().close().await.unwrap();
+            assert!(previous.acquire().is_err());
+            next.connection()
+                .execute_unprepared("SELECT 1")
+                .await
+                .unwrap();
+            assert!(next.acquire().is_ok());
+            next.close().await.unwrap();
+        });
+    }
+}
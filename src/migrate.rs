@@ -0,0 +1,161 @@
+//! Applying SeaORM migrations as part of connecting.
+
+use crate::{ConnectionError, DbConnector};
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+use sea_orm::DbBackend;
+#[cfg(feature = "mysql")]
+use sea_orm::sqlx::{self, MySql, pool::PoolConnection};
+use sea_orm::{ConnectionTrait, DatabaseConnection};
+use sea_orm_migration::MigratorTrait;
+
+/// Postgres advisory lock key held while migrating: `tinmigr` in ASCII.
+#[cfg(feature = "postgres")]
+const LOCK_KEY: i64 = 0x0074_696e_6d69_6772;
+
+/// MySQL named lock held while migrating. Named locks are server-wide, so
+/// the name includes the database; it is cut to the 64 characters MySQL
+/// allows.
+#[cfg(feature = "mysql")]
+const MYSQL_LOCK_NAME: &str = "LEFT(CONCAT('tin-sea-conn:migrate:', DATABASE()), 64)";
+
+/// What [`DbConnector::connect_and_migrate`] applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MigrationReport {
+    /// The names of the migrations applied, in order. Empty when the
+    /// schema was already up to date.
+    pub applied: Vec<String>,
+}
+
+/// The lock that keeps two instances from migrating at once.
+enum MigrationLock {
+    #[cfg(feature = "postgres")]
+    Postgres(crate::AdvisoryLock),
+    #[cfg(feature = "mysql")]
+    MySql(PoolConnection<MySql>),
+    /// SQLite serializes writers itself.
+    None,
+}
+
+impl MigrationLock {
+    async fn acquire(conn: &DatabaseConnection) -> Result<Self, ConnectionError> {
+        match conn.get_database_backend() {
+            #[cfg(feature = "postgres")]
+            DbBackend::Postgres => crate::AdvisoryLock::acquire(conn, LOCK_KEY)
+                .await
+                .map(MigrationLock::Postgres),
+            #[cfg(feature = "mysql")]
+            DbBackend::MySql => {
+                let mut lock = conn
+                    .get_mysql_connection_pool()
+                    .acquire()
+                    .await
+                    .map_err(|e| ConnectionError::ConnectionFailed(e.to_string()))?;
+                // A negative timeout waits for as long as it takes.
+                let locked: Option<i64> =
+                    sqlx::query_scalar(&format!("SELECT GET_LOCK({MYSQL_LOCK_NAME}, -1)"))
+                        .fetch_one(&mut *lock)
+                        .await
+                        .map_err(|e| {
+                            ConnectionError::DatabaseError(format!(
+                                "Taking the migration lock failed: {e}"
+                            ))
+                        })?;
+                if locked != Some(1) {
+                    return Err(ConnectionError::DatabaseError(
+                        "Taking the migration lock failed".into(),
+                    ));
+                }
+                Ok(MigrationLock::MySql(lock))
+            }
+            _ => Ok(MigrationLock::None),
+        }
+    }
+
+    /// Releases the lock. Failing that, the lock's connection is closed,
+    /// which releases it on the server.
+    async fn release(self) {
+        match self {
+            #[cfg(feature = "postgres")]
+            MigrationLock::Postgres(lock) => {
+                if let Err(e) = lock.release().await {
+                    log::warn!("Releasing the migration lock failed: {e}");
+                }
+            }
+            #[cfg(feature = "mysql")]
+            MigrationLock::MySql(mut lock) => {
+                let released = sqlx::query_scalar::<_, Option<i64>>(&format!(
+                    "SELECT RELEASE_LOCK({MYSQL_LOCK_NAME})"
+                ))
+                .fetch_one(&mut *lock)
+                .await;
+                if !matches!(released, Ok(Some(1))) {
+                    log::warn!("Releasing the migration lock failed; closing its connection");
+                    lock.close_on_drop();
+                }
+            }
+            MigrationLock::None => {}
+        }
+    }
+}
+
+impl DbConnector {
+    /// Connects, then applies the migrations of `M` that have not run yet,
+    /// as services otherwise do by hand before serving:
+    ///
+    /// ```ignore
+    /// let (db, report) = connector.connect_and_migrate::<Migrator>().await?;
+    /// log::info!("Applied {:?}", report.applied);
+    /// ```
+    ///
+    /// While migrating, a lock is held so that instances starting together
+    /// take turns: a Postgres advisory lock or a MySQL named lock scoped to
+    /// the database. The instances that wait find nothing left to apply.
+    /// If a migration fails, the pool is closed and the error returned;
+    /// on Postgres the failed run is rolled back as a whole.
+    pub async fn connect_and_migrate<M: MigratorTrait>(
+        self,
+    ) -> Result<(DatabaseConnection, MigrationReport), ConnectionError> {
+        let conn = self.connect().await?;
+        let result = match MigrationLock::acquire(&conn).await {
+            Ok(lock) => {
+                let result = migrate::<M>(&conn).await;
+                lock.release().await;
+                result
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(applied) => Ok((conn, MigrationReport { applied })),
+            Err(e) => {
+                let _ = conn.close().await;
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Applies the pending migrations, returning their names.
+async fn migrate<M: MigratorTrait>(
+    conn: &DatabaseConnection,
+) -> Result<Vec<String>, ConnectionError> {
+    let failed =
+        |e: sea_orm::DbErr| ConnectionError::DatabaseError(format!("Migrating failed: {e}"));
+    let pending: Vec<String> = M::get_pending_migrations(conn)
+        .await
+        .map_err(failed)?
+        .iter()
+        .map(|migration| migration.name().to_string())
+        .collect();
+    if pending.is_empty() {
+        log::debug!("No pending migrations");
+        return Ok(pending);
+    }
+    M::up(conn, None).await.map_err(failed)?;
+    log::info!(
+        "Applied {} migration(s): {}",
+        pending.len(),
+        pending.join(", ")
+    );
+    Ok(pending)
+}
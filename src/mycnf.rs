@@ -0,0 +1,228 @@
+//! Reader for the `[client]` group of MySQL option files (`~/.my.cnf`).
+
+use crate::{ConnectionError, SslMode};
+use std::path::Path;
+
+#[derive(Debug, Default)]
+pub(crate) struct ClientOptions {
+    pub(crate) user: Option<String>,
+    pub(crate) password: Option<String>,
+    pub(crate) host: Option<String>,
+    pub(crate) port: Option<u16>,
+    pub(crate) database: Option<String>,
+    pub(crate) ssl_mode: Option<SslMode>,
+    pub(crate) ssl_ca: Option<String>,
+    pub(crate) ssl_cert: Option<String>,
+    pub(crate) ssl_key: Option<String>,
+}
+
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|v| v.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    value
+}
+
+/// Parses the `[client]` group of an option file. Later occurrences of an
+/// option win, as with the `mysql` client; other groups and `!include`
+/// directives are ignored.
+///
+/// With `mariadb`, the groups and TLS options of the MariaDB client are
+/// read instead: `[client-server]`, `[client-mariadb]` and
+/// `[mariadb-client]` as well, and `ssl`, `ssl-verify-server-cert` and
+/// `skip-ssl` in place of `ssl-mode`, which MariaDB does not have.
+pub(crate) fn read_client_section(
+    path: &Path,
+    mariadb: bool,
+) -> Result<ClientOptions, ConnectionError> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        ConnectionError::InvalidConfig(format!(
+            "Failed to read MySQL option file {}: {e}",
+            path.display()
+        ))
+    })?;
+    parse_client_section(&content, path, mariadb)
+}
+
+/// As [`read_client_section`], on the file's `content`; `path` is only
+/// used in errors.
+fn parse_client_section(
+    content: &str,
+    path: &Path,
+    mariadb: bool,
+) -> Result<ClientOptions, ConnectionError> {
+    let mut options = ClientOptions::default();
+    let mut in_client = false;
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(group) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let group = group.trim().to_ascii_lowercase();
+            in_client = match group.as_str() {
+                "client" => true,
+                "client-server" | "client-mariadb" | "mariadb-client" => mariadb,
+                _ => false,
+            };
+            continue;
+        }
+        if !in_client {
+            continue;
+        }
+        // MariaDB's TLS options are also given as bare flags.
+        let (key, value) = line.split_once('=').unwrap_or((line, "1"));
+        let key = key.trim().replace('_', "-").to_ascii_lowercase();
+        let value = unquote(value).to_string();
+
+        match key.as_str() {
+            "user" => options.user = Some(value),
+            "password" => options.password = Some(value),
+            "host" => options.host = Some(value),
+            "port" => {
+                options.port = Some(value.parse().map_err(|_| {
+                    ConnectionError::InvalidConfig(format!(
+                        "Invalid port in {}: {value}",
+                        path.display()
+                    ))
+                })?)
+            }
+            "database" => options.database = Some(value),
+            "ssl-mode" if !mariadb => options.ssl_mode = Some(value.parse()?),
+            "ssl" | "ssl-verify-server-cert" | "skip-ssl" | "disable-ssl" if mariadb => {
+                options.ssl_mode = mariadb_ssl_mode(&key, &value, options.ssl_mode);
+            }
+            "ssl-ca" => options.ssl_ca = Some(value),
+            "ssl-cert" => options.ssl_cert = Some(value),
+            "ssl-key" => options.ssl_key = Some(value),
+            _ => {}
+        }
+    }
+
+    Ok(options)
+}
+
+/// The TLS mode after a MariaDB `ssl`, `ssl-verify-server-cert` or
+/// `skip-ssl` option. Verification implies TLS, and turning TLS on keeps
+/// verification already asked for.
+fn mariadb_ssl_mode(key: &str, value: &str, current: Option<SslMode>) -> Option<SslMode> {
+    let on = !matches!(value.to_ascii_lowercase().as_str(), "0" | "off" | "false");
+    let enable = match key {
+        "skip-ssl" | "disable-ssl" => !on,
+        _ => on,
+    };
+    match (key, enable) {
+        ("ssl-verify-server-cert", true) => Some(SslMode::VerifyFull),
+        ("ssl-verify-server-cert", false) if current == Some(SslMode::VerifyFull) => {
+            Some(SslMode::Require)
+        }
+        ("ssl-verify-server-cert", false) => current,
+        (_, true) if current == Some(SslMode::VerifyFull) => current,
+        (_, true) => Some(SslMode::Require),
+        (_, false) => Some(SslMode::Disable),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClientOptions, parse_client_section};
+    use crate::SslMode;
+    use std::path::Path;
+
+    fn parse(content: &str, mariadb: bool) -> ClientOptions {
+        parse_client_section(content, Path::new("my.cnf"), mariadb).unwrap()
+    }
+
+    #[test]
+    fn reads_only_the_client_group() {
+        let cases = [
+            // [mysql] is for the command-line client, before or after [client].
+            ("[mysql]\nuser = cli\n[client]\nuser = app\n", Some("app")),
+            ("[client]\nuser = app\n[mysql]\nuser = cli\n", Some("app")),
+            ("[mysqld]\nuser = mysql\n", None),
+            // Options before any group are ignored.
+            ("user = stray\n[client]\n", None),
+            // Group names are case-insensitive.
+            ("[CLIENT]\nuser = app\n", Some("app")),
+            // Later occurrences win, across repeated groups too.
+            (
+                "[client]\nuser = first\n[mysql]\n[client]\nuser = second\n",
+                Some("second"),
+            ),
+        ];
+        for (content, user) in cases {
+            assert_eq!(parse(content, false).user.as_deref(), user, "{content}");
+        }
+    }
+
+    #[test]
+    fn skips_comments_and_unquotes_values() {
+        let options = parse(
+            r#"
+# a comment
+; another comment
+[client]
+user = "app user"
+password = 'p#ss;word'
+host=db.internal
+port = 3307
+database = app
+ssl_ca = /etc/mysql/ca.pem
+"#,
+            false,
+        );
+        assert_eq!(options.user.as_deref(), Some("app user"));
+        assert_eq!(options.password.as_deref(), Some("p#ss;word"));
+        assert_eq!(options.host.as_deref(), Some("db.internal"));
+        assert_eq!(options.port, Some(3307));
+        assert_eq!(options.database.as_deref(), Some("app"));
+        // `_` and `-` are interchangeable in option names.
+        assert_eq!(options.ssl_ca.as_deref(), Some("/etc/mysql/ca.pem"));
+    }
+
+    #[test]
+    fn rejects_an_invalid_port() {
+        let result = parse_client_section("[client]\nport = abc\n", Path::new("my.cnf"), false);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn reads_mysql_ssl_mode() {
+        let options = parse("[client]\nssl-mode = VERIFY_IDENTITY\n", false);
+        assert_eq!(options.ssl_mode, Some(SslMode::VerifyFull));
+    }
+
+    #[test]
+    fn reads_mariadb_groups_and_tls_flags() {
+        let content = "[client-mariadb]\nuser = maria\nssl\n";
+        assert_eq!(parse(content, false).user, None);
+        let options = parse(content, true);
+        assert_eq!(options.user.as_deref(), Some("maria"));
+        assert_eq!(options.ssl_mode, Some(SslMode::Require));
+
+        let cases = [
+            (
+                "[client]\nssl-verify-server-cert\n",
+                Some(SslMode::VerifyFull),
+            ),
+            (
+                "[client]\nssl-verify-server-cert\nssl\n",
+                Some(SslMode::VerifyFull),
+            ),
+            ("[client]\nskip-ssl\n", Some(SslMode::Disable)),
+            ("[client]\nssl = 0\n", Some(SslMode::Disable)),
+            // ssl-mode is MySQL-only.
+            ("[client]\nssl-mode = REQUIRED\n", None),
+        ];
+        for (content, mode) in cases {
+            assert_eq!(parse(content, true).ssl_mode, mode, "{content}");
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use sea_orm::prelude::async_trait::async_trait;
+use sea_orm::{
+    ConnectionTrait, DatabaseConnection, DbBackend, DbErr, ExecResult, QueryResult, Statement,
+};
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// A statement run through an [`ObservedConnection`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct QueryEvent<'a> {
+    /// Set with [`ObservedConnection::label`].
+    pub label: Option<&'a str>,
+    pub sql: &'a str,
+    pub elapsed: Duration,
+    /// Rows affected by `execute`, or returned by `query_one`/`query_all`.
+    /// `None` if the statement failed.
+    pub rows: Option<u64>,
+    pub error: Option<&'a DbErr>,
+}
+
+/// Receives every statement run through an [`ObservedConnection`], e.g. to
+/// feed an APM or a latency histogram.
+///
+/// Called inline after each statement, so it should hand off anything slow.
+/// Closures taking a `&QueryEvent` implement it.
+pub trait QueryObserver: Send + Sync + 'static {
+    fn on_query(&self, event: &QueryEvent<'_>);
+}
+
+impl<F> QueryObserver for F
+where
+    F: Fn(&QueryEvent<'_>) + Send + Sync + 'static,
+{
+    fn on_query(&self, event: &QueryEvent<'_>) {
+        self(event)
+    }
+}
+
+/// Reports every statement made through the returned handle to `observer`.
+///
+/// ```ignore
+/// let db = with_observer(&db, |e: &QueryEvent| {
+///     log::info!("{} took {:?}", e.label.unwrap_or("-"), e.elapsed)
+/// });
+/// let users = User::find().all(&db.label("list_users")).await?;
+/// ```
+pub fn with_observer(
+    conn: &DatabaseConnection,
+    observer: impl QueryObserver,
+) -> ObservedConnection {
+    ObservedConnection {
+        conn: conn.clone(),
+        observer: Arc::new(observer),
+        label: None,
+    }
+}
+
+/// A connection that times each statement and reports it to a
+/// [`QueryObserver`], from [`with_observer`].
+///
+/// It implements sea-orm's `ConnectionTrait`, so entities and raw
+/// statements can run on it directly. Transactions are begun on
+/// [`connection()`](Self::connection) and are not observed.
+#[derive(Clone)]
+pub struct ObservedConnection {
+    conn: DatabaseConnection,
+    observer: Arc<dyn QueryObserver>,
+    label: Option<Arc<str>>,
+}
+
+impl fmt::Debug for ObservedConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ObservedConnection")
+            .field("conn", &self.conn)
+            .field("label", &self.label)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ObservedConnection {
+    /// A handle whose statements are reported with `label`, sharing the
+    /// pool and observer.
+    pub fn label(&self, label: impl AsRef<str>) -> Self {
+        Self {
+            label: Some(label.as_ref().into()),
+            ..self.clone()
+        }
+    }
+
+    /// The connection without observation.
+    pub fn connection(&self) -> &DatabaseConnection {
+        &self.conn
+    }
+
+    async fn observe<T, Fut>(
+        &self,
+        sql: &str,
+        rows: impl FnOnce(&T) -> u64,
+        fut: Fut,
+    ) -> Result<T, DbErr>
+    where
+        Fut: Future<Output = Result<T, DbErr>>,
+    {
+        let started = Instant::now();
+        let result = fut.await;
+        self.observer.on_query(&QueryEvent {
+            label: self.label.as_deref(),
+            sql,
+            elapsed: started.elapsed(),
+            rows: result.as_ref().ok().map(rows),
+            error: result.as_ref().err(),
+        });
+        result
+    }
+}
+
+#[async_trait]
+impl ConnectionTrait for ObservedConnection {
+    fn get_database_backend(&self) -> DbBackend {
+        self.conn.get_database_backend()
+    }
+
+    async fn execute(&self, stmt: Statement) -> Result<ExecResult, DbErr> {
+        let sql = stmt.sql.clone();
+        self.observe(&sql, ExecResult::rows_affected, self.conn.execute(stmt))
+            .await
+    }
+
+    async fn execute_unprepared(&self, sql: &str) -> Result<ExecResult, DbErr> {
+        self.observe(
+            sql,
+            ExecResult::rows_affected,
+            self.conn.execute_unprepared(sql),
+        )
+        .await
+    }
+
+    async fn query_one(&self, stmt: Statement) -> Result<Option<QueryResult>, DbErr> {
+        let sql = stmt.sql.clone();
+        self.observe(&sql, |row| row.is_some() as u64, self.conn.query_one(stmt))
+            .await
+    }
+
+    async fn query_all(&self, stmt: Statement) -> Result<Vec<QueryResult>, DbErr> {
+        let sql = stmt.sql.clone();
+        self.observe(&sql, |rows| rows.len() as u64, self.conn.query_all(stmt))
+            .await
+    }
+
+    fn support_returning(&self) -> bool {
+        self.conn.support_returning()
+    }
+
+    fn is_mock_connection(&self) -> bool {
+        self.conn.is_mock_connection()
+    }
+}
+
+/// Counts statements and failures against the limits set with
+/// [`max_uses_per_connection`](crate::DbConnector::max_uses_per_connection)
+/// and [`retire_after_errors`](crate::DbConnector::retire_after_errors).
+#[derive(Debug)]
+pub(crate) struct RetirementCounter {
+    max_uses: Option<u64>,
+    max_errors: Option<u32>,
+    uses: AtomicU64,
+    errors: AtomicU32,
+    /// Set once a limit is reached, until [`reset`](Self::reset).
+    retiring: AtomicBool,
+}
+
+impl RetirementCounter {
+    /// `None` when neither limit is set.
+    pub(crate) fn new(max_uses: Option<u64>, max_errors: Option<u32>) -> Option<Self> {
+        (max_uses.is_some() || max_errors.is_some()).then(|| Self {
+            max_uses,
+            max_errors,
+            uses: AtomicU64::new(0),
+            errors: AtomicU32::new(0),
+            retiring: AtomicBool::new(false),
+        })
+    }
+
+    /// Counts a statement. Returns the limit it reached, naming the builder
+    /// method, at most once until the counts are [`reset`](Self::reset).
+    pub(crate) fn record(&self, failed: bool) -> Option<&'static str> {
+        let uses = self.uses.fetch_add(1, Ordering::Relaxed) + 1;
+        let errors = if failed {
+            self.errors.fetch_add(1, Ordering::Relaxed) + 1
+        } else {
+            self.errors.load(Ordering::Relaxed)
+        };
+        let reason = if self.max_errors.is_some_and(|max| errors >= max) {
+            "retire_after_errors"
+        } else if self.max_uses.is_some_and(|max| uses >= max) {
+            "max_uses_per_connection"
+        } else {
+            return None;
+        };
+        (!self.retiring.swap(true, Ordering::AcqRel)).then_some(reason)
+    }
+
+    /// Starts counting over, for a new pool.
+    pub(crate) fn reset(&self) {
+        self.uses.store(0, Ordering::Relaxed);
+        self.errors.store(0, Ordering::Relaxed);
+        self.retiring.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetirementCounter;
+
+    #[test]
+    fn retirement_needs_a_limit() {
+        assert!(RetirementCounter::new(None, None).is_none());
+    }
+
+    #[test]
+    fn reaching_max_uses_retires_once() {
+        let counter = RetirementCounter::new(Some(3), None).unwrap();
+        assert_eq!(counter.record(false), None);
+        assert_eq!(counter.record(true), None);
+        assert_eq!(counter.record(false), Some("max_uses_per_connection"));
+        // Statements keep running while the pool is replaced.
+        assert_eq!(counter.record(false), None);
+
+        counter.reset();
+        assert_eq!(counter.record(false), None);
+        assert_eq!(counter.record(false), None);
+        assert_eq!(counter.record(false), Some("max_uses_per_connection"));
+    }
+
+    #[test]
+    fn only_failures_count_toward_retire_after_errors() {
+        let counter = RetirementCounter::new(Some(100), Some(2)).unwrap();
+        assert_eq!(counter.record(true), None);
+        for _ in 0..10 {
+            assert_eq!(counter.record(false), None);
+        }
+        assert_eq!(counter.record(true), Some("retire_after_errors"));
+        assert_eq!(counter.record(true), None);
+    }
+}
@@ -0,0 +1,355 @@
+//! OpenTelemetry metrics and spans for the `otel` feature.
+//!
+//! Everything is recorded through the global providers of the
+//! `opentelemetry` API under the `tin-sea-conn` scope, so nothing is
+//! exported until the application installs an SDK.
+
+use crate::{ConnectionError, DbConnector, ErrorClass, PoolStats, QueryEvent, QueryObserver};
+use opentelemetry::global::{self, BoxedSpan, BoxedTracer};
+use opentelemetry::metrics::Histogram;
+use opentelemetry::trace::{Span, SpanKind, Status, Tracer};
+use opentelemetry::{KeyValue, Value};
+use sea_orm::DatabaseConnection;
+use std::fmt;
+use std::sync::Weak;
+use std::time::{Instant, SystemTime};
+use tokio::sync::watch;
+
+const SCOPE: &str = "tin-sea-conn";
+
+/// Bucket boundaries in seconds for the duration histograms, as recommended
+/// by the semantic conventions. The SDK's defaults are meant for
+/// milliseconds.
+const DURATION_BUCKETS: [f64; 14] = [
+    0.005, 0.01, 0.025, 0.05, 0.075, 0.1, 0.25, 0.5, 0.75, 1.0, 2.5, 5.0, 7.5, 10.0,
+];
+
+/// The database a connector connects to, as semantic-convention
+/// attributes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Target {
+    pub(crate) db_system: &'static str,
+    pub(crate) address: Option<String>,
+    pub(crate) port: Option<u16>,
+    pub(crate) database: Option<String>,
+}
+
+impl Target {
+    fn attributes(&self) -> Vec<KeyValue> {
+        let mut attributes = vec![KeyValue::new("db.system", self.db_system)];
+        if let Some(address) = &self.address {
+            attributes.push(KeyValue::new("server.address", address.clone()));
+        }
+        if let Some(port) = self.port {
+            attributes.push(KeyValue::new("server.port", i64::from(port)));
+        }
+        if let Some(database) = &self.database {
+            attributes.push(KeyValue::new("db.namespace", database.clone()));
+        }
+        attributes
+    }
+
+    /// `db.client.connection.pool.name`: the address, port and database,
+    /// e.g. `db-1:5432/app`.
+    fn pool_name(&self) -> String {
+        let mut name = String::new();
+        match (&self.address, self.port) {
+            (Some(address), Some(port)) if address.contains(':') => {
+                name.push_str(&format!("[{address}]:{port}"))
+            }
+            (Some(address), Some(port)) => name.push_str(&format!("{address}:{port}")),
+            (Some(address), None) => name.push_str(address),
+            (None, _) => {}
+        }
+        if let Some(database) = &self.database {
+            if !name.is_empty() {
+                name.push('/');
+            }
+            name.push_str(database);
+        }
+        if name.is_empty() {
+            name.push_str(self.db_system);
+        }
+        name
+    }
+}
+
+fn duration_histogram(name: &'static str, description: &'static str) -> Histogram<f64> {
+    global::meter(SCOPE)
+        .f64_histogram(name)
+        .with_unit("s")
+        .with_description(description)
+        .with_boundaries(DURATION_BUCKETS.to_vec())
+        .build()
+}
+
+/// One host tried by a `connect()`: a `connect` span from start to finish,
+/// then the attempt, failure and latency metrics.
+pub(crate) struct ConnectAttempt {
+    attributes: Vec<KeyValue>,
+    span: BoxedSpan,
+    started: Instant,
+}
+
+impl ConnectAttempt {
+    pub(crate) fn start(target: &Target, attempt: usize) -> Self {
+        let attributes = target.attributes();
+        let tracer = global::tracer(SCOPE);
+        let span = tracer
+            .span_builder("connect")
+            .with_kind(SpanKind::Client)
+            .with_attributes(
+                attributes
+                    .iter()
+                    .cloned()
+                    .chain([KeyValue::new("connect.attempt", attempt as i64)]),
+            )
+            .start(&tracer);
+        Self {
+            attributes,
+            span,
+            started: Instant::now(),
+        }
+    }
+
+    pub(crate) fn finish(self, result: &Result<DatabaseConnection, ConnectionError>) {
+        let Self {
+            mut attributes,
+            mut span,
+            started,
+        } = self;
+        let meter = global::meter(SCOPE);
+        meter
+            .u64_counter("db.client.connection.attempts")
+            .with_unit("{attempt}")
+            .with_description("Connection attempts, one per host tried.")
+            .build()
+            .add(1, &attributes);
+        if let Err(e) = result {
+            let code = e.code().as_str();
+            attributes.push(KeyValue::new("error.type", code));
+            meter
+                .u64_counter("db.client.connection.failures")
+                .with_unit("{attempt}")
+                .with_description("Failed connection attempts, by error.type.")
+                .build()
+                .add(1, &attributes);
+            span.set_attribute(KeyValue::new("error.type", code));
+            span.set_status(Status::error(code));
+        }
+        duration_histogram(
+            "db.client.connection.create_time",
+            "The time it took to connect to a host.",
+        )
+        .record(started.elapsed().as_secs_f64(), &attributes);
+        span.end();
+    }
+}
+
+/// Registers the `db.client.connection.count` and
+/// `db.client.connection.max` gauges for a managed pool. They stop
+/// reporting once the pool has been dropped.
+pub(crate) fn register_pool(target: &Target, pool: Weak<watch::Sender<DatabaseConnection>>) {
+    let mut attributes = target.attributes();
+    attributes.push(KeyValue::new(
+        "db.client.connection.pool.name",
+        target.pool_name(),
+    ));
+    let stats = {
+        let pool = pool.clone();
+        move || {
+            pool.upgrade()
+                .map(|current| PoolStats::of(&current.borrow()))
+        }
+    };
+    let meter = global::meter(SCOPE);
+    let count_attributes = attributes.clone();
+    let count_stats = stats.clone();
+    meter
+        .u64_observable_gauge("db.client.connection.count")
+        .with_unit("{connection}")
+        .with_description("Open connections, by db.client.connection.state.")
+        .with_callback(move |observer| {
+            if let Some(stats) = count_stats() {
+                for (state, count) in [
+                    ("idle", stats.idle),
+                    ("used", stats.size.saturating_sub(stats.idle)),
+                ] {
+                    let mut attributes = count_attributes.clone();
+                    attributes.push(KeyValue::new("db.client.connection.state", state));
+                    observer.observe(u64::from(count), &attributes);
+                }
+            }
+        })
+        .build();
+    meter
+        .u64_observable_gauge("db.client.connection.max")
+        .with_unit("{connection}")
+        .with_description("The maximum number of open connections allowed.")
+        .with_callback(move |observer| {
+            if let Some(stats) = stats() {
+                observer.observe(u64::from(stats.max_connections), &attributes);
+            }
+        })
+        .build();
+}
+
+/// A [`QueryObserver`] that exports each statement as an OpenTelemetry
+/// client span and records it in the `db.client.operation.duration`
+/// histogram.
+///
+/// Spans are named after the statement's label, or its first keyword, and
+/// carry `db.system`, `server.address`, `server.port`, `db.namespace`,
+/// `db.operation.name` and `db.query.text`; failed statements also get
+/// `error.type` and an error status. The histogram leaves out the query
+/// text to keep its cardinality low.
+///
+/// ```ignore
+/// let db = with_observer(&conn, OtelObserver::new(&connector));
+/// let users = User::find().all(&db.label("list_users")).await?;
+/// ```
+pub struct OtelObserver {
+    attributes: Vec<KeyValue>,
+    tracer: BoxedTracer,
+    duration: Histogram<f64>,
+}
+
+impl OtelObserver {
+    /// Instruments created from the global providers installed at the time
+    /// of the call.
+    pub fn new(connector: &DbConnector) -> Self {
+        Self {
+            attributes: connector.otel_target().attributes(),
+            tracer: global::tracer(SCOPE),
+            duration: duration_histogram(
+                "db.client.operation.duration",
+                "Duration of database client operations.",
+            ),
+        }
+    }
+}
+
+impl fmt::Debug for OtelObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OtelObserver")
+            .field("attributes", &self.attributes)
+            .finish_non_exhaustive()
+    }
+}
+
+impl QueryObserver for OtelObserver {
+    fn on_query(&self, event: &QueryEvent<'_>) {
+        let operation = operation_name(event.sql);
+        let mut attributes = self.attributes.clone();
+        if let Some(operation) = &operation {
+            attributes.push(KeyValue::new("db.operation.name", operation.clone()));
+        }
+        if let Some(e) = event.error {
+            attributes.push(KeyValue::new(
+                "error.type",
+                error_class_name(crate::classify(e)),
+            ));
+        }
+        self.duration
+            .record(event.elapsed.as_secs_f64(), &attributes);
+
+        let name = match (event.label, &operation) {
+            (Some(label), _) => label.to_string(),
+            (None, Some(operation)) => operation.clone(),
+            (None, None) => "query".into(),
+        };
+        let mut span_attributes = attributes;
+        span_attributes.push(KeyValue::new("db.query.text", event.sql.to_string()));
+        if let Some(rows) = event.rows {
+            span_attributes.push(KeyValue::new("db.response.returned_rows", rows as i64));
+        }
+        let end = SystemTime::now();
+        let mut span = self
+            .tracer
+            .span_builder(name)
+            .with_kind(SpanKind::Client)
+            .with_start_time(end.checked_sub(event.elapsed).unwrap_or(end))
+            .with_attributes(span_attributes)
+            .start(&self.tracer);
+        if let Some(e) = event.error {
+            span.set_status(Status::error(e.to_string()));
+        }
+        span.end_with_timestamp(end);
+    }
+}
+
+/// The statement's first keyword in upper case, e.g. `SELECT`.
+fn operation_name(sql: &str) -> Option<String> {
+    let keyword = sql
+        .trim_start_matches(|c: char| c.is_whitespace() || c == '(')
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .next()
+        .filter(|keyword| !keyword.is_empty())?;
+    Some(keyword.to_ascii_uppercase())
+}
+
+fn error_class_name(class: ErrorClass) -> Value {
+    match class {
+        ErrorClass::Conflict => "conflict",
+        ErrorClass::Transient => "transient",
+        ErrorClass::Permanent => "permanent",
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Target, operation_name};
+    use opentelemetry::KeyValue;
+
+    #[test]
+    fn attributes_follow_the_semantic_conventions() {
+        let target = Target {
+            db_system: "postgresql",
+            address: Some("db-1".into()),
+            port: Some(5432),
+            database: Some("app".into()),
+        };
+        assert_eq!(
+            target.attributes(),
+            [
+                KeyValue::new("db.system", "postgresql"),
+                KeyValue::new("server.address", "db-1"),
+                KeyValue::new("server.port", 5432),
+                KeyValue::new("db.namespace", "app"),
+            ]
+        );
+        assert_eq!(target.pool_name(), "db-1:5432/app");
+
+        let sqlite = Target {
+            db_system: "sqlite",
+            database: Some("data/app.db".into()),
+            ..Target::default()
+        };
+        assert_eq!(sqlite.attributes().len(), 2);
+        assert_eq!(sqlite.pool_name(), "data/app.db");
+
+        let ipv6 = Target {
+            db_system: "mysql",
+            address: Some("::1".into()),
+            port: Some(3306),
+            database: None,
+        };
+        assert_eq!(ipv6.pool_name(), "[::1]:3306");
+    }
+
+    #[test]
+    fn names_operations_by_their_first_keyword() {
+        let cases = [
+            ("SELECT 1", Some("SELECT")),
+            ("  insert into t values (1)", Some("INSERT")),
+            ("(select 1) union (select 2)", Some("SELECT")),
+            ("WITH x AS (SELECT 1) SELECT * FROM x", Some("WITH")),
+            ("", None),
+            ("-- comment", None),
+        ];
+        for (sql, operation) in cases {
+            assert_eq!(operation_name(sql).as_deref(), operation, "{sql}");
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! Password lookup from the libpq password file (`~/.pgpass`).
+
+use crate::ConnectionError;
+use std::path::PathBuf;
+
+fn pgpass_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("PGPASSFILE") {
+        return Some(PathBuf::from(path));
+    }
+    #[cfg(windows)]
+    {
+        std::env::var_os("APPDATA")
+            .map(|dir| PathBuf::from(dir).join("postgresql").join("pgpass.conf"))
+    }
+    #[cfg(not(windows))]
+    {
+        std::env::var_os("HOME").map(|dir| PathBuf::from(dir).join(".pgpass"))
+    }
+}
+
+/// Splits a pgpass line on unescaped `:`, resolving `\:` and `\\` escapes.
+/// Everything after the fourth separator belongs to the password.
+fn split_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(next) = chars.next() {
+                    fields.last_mut().unwrap().push(next);
+                }
+            }
+            ':' if fields.len() < 5 => fields.push(String::new()),
+            _ => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+fn field_matches(pattern: &str, value: &str) -> bool {
+    pattern == "*" || pattern == value
+}
+
+/// Looks up the password for a connection using libpq's matching rules: the
+/// first line whose `hostname:port:database:username` fields all match (with
+/// `*` as a wildcard) wins.
+///
+/// Returns `Ok(None)` when there is no file or no matching entry. On Unix the
+/// file is ignored if it is readable by group or others, as libpq does.
+pub(crate) fn lookup(
+    host: &str,
+    port: u16,
+    database: &str,
+    username: &str,
+) -> Result<Option<String>, ConnectionError> {
+    let Some(path) = pgpass_path() else {
+        return Ok(None);
+    };
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path)
+            .map_err(|e| {
+                ConnectionError::InvalidConfig(format!("Failed to read {}: {e}", path.display()))
+            })?
+            .permissions()
+            .mode();
+        if mode & 0o077 != 0 {
+            log::warn!(
+                "Password file {} has group or world access; permissions should be u=rw (0600) or less",
+                path.display()
+            );
+            return Ok(None);
+        }
+    }
+
+    let content = std::fs::read_to_string(&path).map_err(|e| {
+        ConnectionError::InvalidConfig(format!("Failed to read {}: {e}", path.display()))
+    })?;
+    Ok(find_password(&content, host, port, database, username))
+}
+
+/// The password of the first line of `content` matching the connection.
+fn find_password(
+    content: &str,
+    host: &str,
+    port: u16,
+    database: &str,
+    username: &str,
+) -> Option<String> {
+    let port = port.to_string();
+    for line in content.lines() {
+        if line.trim_start().starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        let fields = split_fields(line);
+        if fields.len() < 5 {
+            continue;
+        }
+        if field_matches(&fields[0], host)
+            && field_matches(&fields[1], &port)
+            && field_matches(&fields[2], database)
+            && field_matches(&fields[3], username)
+        {
+            return Some(fields[4].clone());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_password, split_fields};
+
+    #[test]
+    fn split_fields_resolves_escapes() {
+        let cases: &[(&str, &[&str])] = &[
+            (
+                "db:5432:app:alice:s3cret",
+                &["db", "5432", "app", "alice", "s3cret"],
+            ),
+            (
+                r"db:5432:app:alice:pa\:ss",
+                &["db", "5432", "app", "alice", "pa:ss"],
+            ),
+            (
+                r"db:5432:my\:db:alice:pw",
+                &["db", "5432", "my:db", "alice", "pw"],
+            ),
+            (
+                r"db:5432:app:alice:back\\slash",
+                &["db", "5432", "app", "alice", r"back\slash"],
+            ),
+            // Separators after the fourth belong to the password.
+            (
+                "db:5432:app:alice:a:b:c",
+                &["db", "5432", "app", "alice", "a:b:c"],
+            ),
+            ("db:5432:app", &["db", "5432", "app"]),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(split_fields(line), *expected, "{line}");
+        }
+    }
+
+    #[test]
+    fn find_password_matches_like_libpq() {
+        let content = r"
+# comment lines and blank lines are skipped
+  # even indented
+db.internal:5432:app:alice:exact
+db.internal:*:app:bob:any-port
+*:5432:*:carol:any-host-and-db
+db.internal:5432:*:*:fallback
+*:*:*:alice:never-reached
+too:few:fields
+my\:host:5432:app:dave:escaped-host
+";
+        let cases = [
+            (("db.internal", 5432, "app", "alice"), Some("exact")),
+            (("db.internal", 6432, "app", "bob"), Some("any-port")),
+            (("other", 5432, "reports", "carol"), Some("any-host-and-db")),
+            (("db.internal", 5432, "reports", "erin"), Some("fallback")),
+            // The first matching line wins.
+            (
+                ("db.internal", 5432, "app", "carol"),
+                Some("any-host-and-db"),
+            ),
+            (("other", 6432, "app", "alice"), Some("never-reached")),
+            (("my:host", 5432, "app", "dave"), Some("escaped-host")),
+            (("other", 6432, "app", "frank"), None),
+        ];
+        for ((host, port, database, user), expected) in cases {
+            assert_eq!(
+                find_password(content, host, port, database, user).as_deref(),
+                expected,
+                "{host}:{port}:{database}:{user}"
+            );
+        }
+    }
+
+    #[test]
+    fn find_password_returns_escaped_password() {
+        let content = r"db:5432:app:alice:p\:w\\d";
+        assert_eq!(
+            find_password(content, "db", 5432, "app", "alice").as_deref(),
+            Some(r"p:w\d")
+        );
+    }
+}
@@ -0,0 +1,92 @@
+//! Opening pools with sqlx directly, for settings sea-orm's
+//! `ConnectOptions` has no place for.
+
+use crate::{DatabaseType, DbConnector};
+use sea_orm::sqlx::{self, Connection, Executor};
+use sea_orm::{ConnectOptions, DatabaseConnection, DbErr, RuntimeErr};
+use std::sync::Arc;
+
+/// Like `Database::connect`, additionally running the connector's session
+/// variable and `after_connect` statements on every new physical
+/// connection.
+///
+/// Statement logging follows `opt`; the backend adjustments come from the
+/// connector, as `connect_options()` only hands them to sea-orm.
+pub(crate) async fn connect(
+    connector: &DbConnector,
+    opt: ConnectOptions,
+) -> Result<DatabaseConnection, DbErr> {
+    open(connector, opt)
+        .await
+        .map_err(|e| DbErr::Conn(RuntimeErr::SqlxError(e)))
+}
+
+async fn open(
+    connector: &DbConnector,
+    opt: ConnectOptions,
+) -> Result<DatabaseConnection, sqlx::Error> {
+    let url = opt.get_url().to_owned();
+    let logging = opt.get_sqlx_logging().then(|| opt.get_sqlx_logging_level());
+    let statements: Arc<[String]> = connector.connection_init_sql().into();
+
+    match connector.db_type() {
+        #[cfg(feature = "postgres")]
+        Some(DatabaseType::PostgreSQL) => {
+            let options = connector.pg_opts()(with_logging(url.parse()?, logging));
+            let pool = open_pool(opt, options, statements).await?;
+            Ok(sea_orm::SqlxPostgresConnector::from_sqlx_postgres_pool(
+                pool,
+            ))
+        }
+        #[cfg(feature = "mysql")]
+        Some(DatabaseType::MySQL | DatabaseType::MariaDB) => {
+            let options = connector.mysql_opts()(with_logging(url.parse()?, logging));
+            let pool = open_pool(opt, options, statements).await?;
+            Ok(sea_orm::SqlxMySqlConnector::from_sqlx_mysql_pool(pool))
+        }
+        #[cfg(feature = "sqlite")]
+        Some(DatabaseType::SQLite) => {
+            let options = connector.sqlite_opts()(with_logging(url.parse()?, logging));
+            let pool = open_pool(opt, options, statements).await?;
+            Ok(sea_orm::SqlxSqliteConnector::from_sqlx_sqlite_pool(pool))
+        }
+        _ => Err(sqlx::Error::Configuration(
+            "Database type is required".into(),
+        )),
+    }
+}
+
+fn with_logging<O: sqlx::ConnectOptions>(options: O, level: Option<log::LevelFilter>) -> O {
+    match level {
+        Some(level) => options.log_statements(level),
+        None => options.disable_statement_logging(),
+    }
+}
+
+async fn open_pool<DB>(
+    opt: ConnectOptions,
+    options: <DB::Connection as Connection>::Options,
+    statements: Arc<[String]>,
+) -> Result<sqlx::Pool<DB>, sqlx::Error>
+where
+    DB: sqlx::Database,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+{
+    opt.sqlx_pool_options::<DB>()
+        .after_connect(move |conn, _| {
+            let statements = statements.clone();
+            Box::pin(async move {
+                for sql in statements.iter() {
+                    // sqlx retries failed connections until the connect
+                    // timeout and then reports only the timeout.
+                    if let Err(e) = conn.execute(sql.as_str()).await {
+                        log::warn!("Connection setup statement `{sql}` failed: {e}");
+                        return Err(e);
+                    }
+                }
+                Ok(())
+            })
+        })
+        .connect_with(options)
+        .await
+}
@@ -0,0 +1,151 @@
+//! Pool and connection metrics for the `metrics` feature.
+//!
+//! Recorded through the `metrics` facade, so they are exported by whichever
+//! recorder the application installed, e.g. `metrics-exporter-prometheus`.
+
+use crate::{ConnectionError, PoolStats, QueryEvent, QueryObserver};
+use metrics::{Label, Unit};
+use sea_orm::DatabaseConnection;
+use std::time::{Duration, Instant};
+
+/// How often the pool is sampled.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+/// The longest a sampled checkout is waited for; a longer wait is reported
+/// as this.
+const ACQUIRE_WAIT_LIMIT: Duration = Duration::from_secs(5);
+
+fn describe() {
+    metrics::describe_gauge!(
+        "connections_open",
+        Unit::Count,
+        "Open connections in the pool, idle or in use."
+    );
+    metrics::describe_gauge!(
+        "connections_idle",
+        Unit::Count,
+        "Idle connections in the pool."
+    );
+    metrics::describe_gauge!(
+        "connections_in_use",
+        Unit::Count,
+        "Connections checked out of the pool."
+    );
+    metrics::describe_gauge!(
+        "connections_max",
+        Unit::Count,
+        "The most connections the pool opens."
+    );
+    metrics::describe_gauge!(
+        "acquire_wait_seconds",
+        Unit::Seconds,
+        "How long a checkout waits for a connection, sampled every second; zero while one is free."
+    );
+    metrics::describe_counter!(
+        "connect_errors_total",
+        Unit::Count,
+        "Failed connection attempts, by error code."
+    );
+}
+
+/// Samples the pool behind `conn` every second into the pool gauges,
+/// labelled with `labels`. Once the pool is closed the gauges are zeroed
+/// and the task ends.
+pub(crate) fn spawn(conn: &DatabaseConnection, labels: Vec<(&'static str, String)>) {
+    describe();
+    let conn = conn.clone();
+    crate::tasks::spawn("tin-sea-conn::pool-metrics", async move {
+        let open = metrics::gauge!("connections_open", &labels);
+        let idle = metrics::gauge!("connections_idle", &labels);
+        let in_use = metrics::gauge!("connections_in_use", &labels);
+        let max = metrics::gauge!("connections_max", &labels);
+        let acquire_wait = metrics::gauge!("acquire_wait_seconds", &labels);
+        while !crate::managed::is_closed(&conn) {
+            let stats = PoolStats::of(&conn);
+            open.set(stats.size);
+            idle.set(stats.idle);
+            in_use.set(stats.in_use());
+            max.set(stats.max_connections);
+            // Callers only wait once every connection is open and busy.
+            // Then queue up behind them, reporting the wait so far each
+            // second until a connection comes free.
+            let wait = if stats.is_full() {
+                let started = Instant::now();
+                let mut probe =
+                    std::pin::pin!(crate::saturation::acquire_wait(&conn, ACQUIRE_WAIT_LIMIT));
+                loop {
+                    match tokio::time::timeout(SAMPLE_INTERVAL, probe.as_mut()).await {
+                        Ok(wait) => break wait.unwrap_or(ACQUIRE_WAIT_LIMIT),
+                        Err(_) => acquire_wait.set(started.elapsed()),
+                    }
+                }
+            } else {
+                Duration::ZERO
+            };
+            acquire_wait.set(wait);
+            tokio::time::sleep(SAMPLE_INTERVAL).await;
+        }
+        for gauge in [open, idle, in_use, max, acquire_wait] {
+            gauge.set(0.0);
+        }
+    });
+}
+
+/// Counts a failed connection attempt in `connect_errors_total`.
+pub(crate) fn connect_failed(labels: &[(&'static str, String)], error: &ConnectionError) {
+    let labels: Vec<Label> = labels
+        .iter()
+        .map(|(key, value)| Label::new(*key, value.clone()))
+        .chain([Label::new("error", error.code().as_str())])
+        .collect();
+    metrics::counter!("connect_errors_total", labels).increment(1);
+}
+
+/// A [`QueryObserver`] that records each statement in the
+/// `query_duration_seconds` histogram of the `metrics` facade.
+///
+/// Series are labelled with `db_system`, `server_address` and `database`,
+/// the statement's label (`query`, empty if it has none) and `outcome`
+/// (`ok` or `error`). The SQL itself is left out to keep the number of
+/// series down.
+///
+/// ```ignore
+/// let db = with_observer(&conn, MetricsObserver::new(&connector));
+/// let users = User::find().all(&db.label("list_users")).await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct MetricsObserver {
+    labels: Vec<Label>,
+}
+
+impl MetricsObserver {
+    pub fn new(connector: &crate::DbConnector) -> Self {
+        metrics::describe_histogram!(
+            "query_duration_seconds",
+            Unit::Seconds,
+            "Duration of statements run through a MetricsObserver."
+        );
+        Self {
+            labels: connector
+                .metric_labels()
+                .into_iter()
+                .map(|(key, value)| Label::new(key, value))
+                .collect(),
+        }
+    }
+}
+
+impl QueryObserver for MetricsObserver {
+    fn on_query(&self, event: &QueryEvent<'_>) {
+        let outcome = if event.error.is_some() { "error" } else { "ok" };
+        let labels: Vec<Label> = self
+            .labels
+            .iter()
+            .cloned()
+            .chain([
+                Label::new("query", event.label.unwrap_or_default().to_string()),
+                Label::new("outcome", outcome),
+            ])
+            .collect();
+        metrics::histogram!("query_duration_seconds", labels).record(event.elapsed);
+    }
+}
@@ -0,0 +1,267 @@
+//! Step-by-step connectivity checks, for telling a DNS problem from a
+//! firewall from a bad password.
+
+use crate::{ConnectionError, DbConnector, ErrorCode};
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// A step of a [`preflight`](DbConnector::preflight) check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PreflightStage {
+    /// Resolving the host name.
+    Dns,
+    /// Opening a TCP connection to the resolved addresses.
+    Tcp,
+    /// Opening a database connection: the TLS handshake, if TLS is on, and
+    /// authentication.
+    Login,
+}
+
+impl PreflightStage {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PreflightStage::Dns => "dns",
+            PreflightStage::Tcp => "tcp",
+            PreflightStage::Login => "login",
+        }
+    }
+}
+
+/// What a [`preflight`](DbConnector::preflight) check found wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PreflightFailure {
+    /// The host name does not resolve.
+    Dns,
+    /// No resolved address accepts TCP connections in time: a firewall,
+    /// routing, a wrong port or a server that is down.
+    Network,
+    Tls,
+    Authentication,
+    /// The server was reached and logged into, but refused the connection
+    /// otherwise, e.g. because the database does not exist.
+    Database,
+    /// The settings are incomplete or inconsistent.
+    Config,
+}
+
+impl PreflightFailure {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PreflightFailure::Dns => "dns",
+            PreflightFailure::Network => "network",
+            PreflightFailure::Tls => "tls",
+            PreflightFailure::Authentication => "authentication",
+            PreflightFailure::Database => "database",
+            PreflightFailure::Config => "config",
+        }
+    }
+
+    fn of(error: &ConnectionError) -> Self {
+        match error.code() {
+            ErrorCode::DnsResolution => PreflightFailure::Dns,
+            ErrorCode::ConnectionFailed | ErrorCode::ConnectTimeout => PreflightFailure::Network,
+            ErrorCode::TlsHandshakeFailed => PreflightFailure::Tls,
+            ErrorCode::AuthenticationFailed => PreflightFailure::Authentication,
+            ErrorCode::InvalidConfig | ErrorCode::MissingField => PreflightFailure::Config,
+            _ => PreflightFailure::Database,
+        }
+    }
+}
+
+/// A step that was run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PreflightStep {
+    pub stage: PreflightStage,
+    pub elapsed: Duration,
+    /// Why the step failed.
+    pub error: Option<String>,
+}
+
+/// The outcome of [`DbConnector::preflight`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PreflightReport {
+    /// The host checked, when there is one.
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    /// What the host resolved to.
+    pub addresses: Vec<SocketAddr>,
+    /// The address that accepted a TCP connection.
+    pub connected_address: Option<SocketAddr>,
+    /// The steps run, in order, up to and including the first failure.
+    pub steps: Vec<PreflightStep>,
+    pub failure: Option<PreflightFailure>,
+}
+
+impl PreflightReport {
+    /// Every step succeeded.
+    pub fn is_ok(&self) -> bool {
+        self.failure.is_none()
+    }
+
+    fn record(
+        &mut self,
+        stage: PreflightStage,
+        started: Instant,
+        result: Result<(), (PreflightFailure, String)>,
+    ) -> bool {
+        let (failure, error) = match result {
+            Ok(()) => (None, None),
+            Err((failure, error)) => (Some(failure), Some(error)),
+        };
+        self.steps.push(PreflightStep {
+            stage,
+            elapsed: started.elapsed(),
+            error,
+        });
+        self.failure = failure;
+        failure.is_none()
+    }
+}
+
+/// One line per step, e.g. `tcp   failed after 5.000s: 10.0.0.5:5432: timed out after 5s`.
+impl fmt::Display for PreflightReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.host, self.port) {
+            (Some(host), Some(port)) => writeln!(f, "preflight {host}:{port}")?,
+            (Some(host), None) => writeln!(f, "preflight {host}")?,
+            _ => writeln!(f, "preflight")?,
+        }
+        for step in &self.steps {
+            let stage = step.stage.as_str();
+            let elapsed = step.elapsed.as_secs_f64();
+            match &step.error {
+                None => write!(f, "{stage:<5} ok in {elapsed:.3}s")?,
+                Some(error) => write!(f, "{stage:<5} failed after {elapsed:.3}s: {error}")?,
+            }
+            match step.stage {
+                PreflightStage::Dns if step.error.is_none() => {
+                    let addresses: Vec<String> =
+                        self.addresses.iter().map(|a| a.ip().to_string()).collect();
+                    writeln!(f, " ({})", addresses.join(", "))?
+                }
+                PreflightStage::Tcp if step.error.is_none() => match self.connected_address {
+                    Some(address) => writeln!(f, " ({address})")?,
+                    None => writeln!(f)?,
+                },
+                _ => writeln!(f)?,
+            }
+        }
+        match self.failure {
+            Some(failure) => write!(f, "result: {} failure", failure.as_str()),
+            None => write!(f, "result: ok"),
+        }
+    }
+}
+
+impl DbConnector {
+    /// Checks, step by step, that the database can be reached, without
+    /// opening a pool: resolves the host, opens a TCP connection to it, then
+    /// logs in over a single connection, which includes the TLS handshake
+    /// when TLS is on. Stops at the first step that fails and reports which
+    /// kind of failure it was, so a container that cannot resolve or reach
+    /// the database is told apart from one with a wrong password.
+    ///
+    /// The DNS and TCP steps are skipped for SQLite, Unix sockets and when
+    /// the host is reached through an SSH tunnel or SOCKS5 proxy. Each step
+    /// is bounded by `connect_timeout`.
+    pub async fn preflight(&self) -> PreflightReport {
+        let mut report = PreflightReport {
+            host: None,
+            port: None,
+            addresses: Vec::new(),
+            connected_address: None,
+            steps: Vec::new(),
+            failure: None,
+        };
+        let config = match self.effective_config() {
+            Ok(config) => config,
+            Err(e) => {
+                report.record(
+                    PreflightStage::Login,
+                    Instant::now(),
+                    Err((PreflightFailure::Config, e.to_string())),
+                );
+                return report;
+            }
+        };
+        report.host = config.host;
+        report.port = config.port;
+
+        #[cfg(any(feature = "postgres", feature = "mysql"))]
+        if let (Some(host), Some(port)) = (report.host.clone(), report.port)
+            && self.reaches_host_directly()
+        {
+            let timeout = Duration::from_secs(config.connect_timeout);
+            let started = Instant::now();
+            let result = match resolve(&host, port, timeout).await {
+                Ok(addresses) => {
+                    report.addresses = addresses;
+                    Ok(())
+                }
+                Err(e) => Err((PreflightFailure::Dns, e)),
+            };
+            if !report.record(PreflightStage::Dns, started, result) {
+                return report;
+            }
+
+            let started = Instant::now();
+            let result = match connect_tcp(&report.addresses, timeout).await {
+                Ok(address) => {
+                    report.connected_address = Some(address);
+                    Ok(())
+                }
+                Err(e) => Err((PreflightFailure::Network, e)),
+            };
+            if !report.record(PreflightStage::Tcp, started, result) {
+                return report;
+            }
+        }
+
+        let started = Instant::now();
+        // sqlx bounds the login by `connect_timeout` itself.
+        let result = match self.single_connection().connect_any_host().await {
+            Ok((conn, _)) => {
+                let _ = conn.close().await;
+                Ok(())
+            }
+            Err(e) => Err((PreflightFailure::of(&e), e.to_string())),
+        };
+        report.record(PreflightStage::Login, started, result);
+        report
+    }
+}
+
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+async fn resolve(host: &str, port: u16, timeout: Duration) -> Result<Vec<SocketAddr>, String> {
+    match tokio::time::timeout(timeout, tokio::net::lookup_host((host, port))).await {
+        Ok(Ok(addresses)) => {
+            let addresses: Vec<SocketAddr> = addresses.collect();
+            if addresses.is_empty() {
+                Err(format!("{host} has no addresses"))
+            } else {
+                Ok(addresses)
+            }
+        }
+        Ok(Err(e)) => Err(format!("{host}: {e}")),
+        Err(_) => Err(format!("{host}: no answer within {timeout:?}")),
+    }
+}
+
+/// Tries each address in turn, returning the first that accepts.
+#[cfg(any(feature = "postgres", feature = "mysql"))]
+async fn connect_tcp(addresses: &[SocketAddr], timeout: Duration) -> Result<SocketAddr, String> {
+    let mut errors = Vec::new();
+    for &address in addresses {
+        match tokio::time::timeout(timeout, tokio::net::TcpStream::connect(address)).await {
+            Ok(Ok(_)) => return Ok(address),
+            Ok(Err(e)) => errors.push(format!("{address}: {e}")),
+            Err(_) => errors.push(format!("{address}: timed out after {timeout:?}")),
+        }
+    }
+    Err(errors.join("; "))
+}
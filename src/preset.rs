@@ -0,0 +1,44 @@
+use crate::DbConnector;
+
+/// A reusable set of builder settings, applied with
+/// [`DbConnector::with_preset`]. Implement it to share an organisation's
+/// standards (TLS, pool sizes, timeouts) as a component rather than a
+/// copied builder chain:
+///
+/// ```ignore
+/// struct Internal;
+///
+/// impl ConnectorPreset for Internal {
+///     fn apply(self, builder: DbConnector) -> DbConnector {
+///         builder
+///             .ssl_mode(SslMode::VerifyFull)
+///             .ssl_root_cert("/etc/ssl/internal-ca.pem")
+///             .max_connections(20)
+///             .connect_timeout(5)
+///     }
+/// }
+///
+/// let conn = DbConnector::new().postgres().with_preset(Internal).host("db1").connect().await?;
+/// ```
+///
+/// Closures taking and returning a `DbConnector` are presets too.
+pub trait ConnectorPreset {
+    fn apply(self, builder: DbConnector) -> DbConnector;
+}
+
+impl<F> ConnectorPreset for F
+where
+    F: FnOnce(DbConnector) -> DbConnector,
+{
+    fn apply(self, builder: DbConnector) -> DbConnector {
+        self(builder)
+    }
+}
+
+impl DbConnector {
+    /// Applies `preset` to the settings so far. Builder calls after it
+    /// override what the preset set, and calls before it are overridden.
+    pub fn with_preset(self, preset: impl ConnectorPreset) -> Self {
+        preset.apply(self)
+    }
+}
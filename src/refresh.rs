@@ -0,0 +1,63 @@
+use crate::ConnectionError;
+use sea_orm::DatabaseConnection;
+use std::future::Future;
+use std::time::Duration;
+
+/// Installs new credentials in the pool's connect options. Connections
+/// opened from now on use them; existing connections are unaffected.
+pub(crate) fn set_credentials(conn: &DatabaseConnection, username: Option<&str>, password: &str) {
+    match conn {
+        #[cfg(feature = "postgres")]
+        DatabaseConnection::SqlxPostgresPoolConnection(_) => {
+            let pool = conn.get_postgres_connection_pool();
+            let mut options = pool.connect_options().as_ref().clone().password(password);
+            if let Some(username) = username {
+                options = options.username(username);
+            }
+            pool.set_connect_options(options);
+        }
+        #[cfg(feature = "mysql")]
+        DatabaseConnection::SqlxMySqlPoolConnection(_) => {
+            let pool = conn.get_mysql_connection_pool();
+            let mut options = pool.connect_options().as_ref().clone().password(password);
+            if let Some(username) = username {
+                options = options.username(username);
+            }
+            pool.set_connect_options(options);
+        }
+        _ => {}
+    }
+}
+
+/// Periodically fetches a new password and installs it in the pool's connect
+/// options, so connections opened after a short-lived token expires still
+/// authenticate.
+///
+/// The task is registered under `name` and ends once the pool is closed.
+#[cfg_attr(
+    not(any(feature = "aws-iam", feature = "gcp", feature = "azure")),
+    allow(dead_code)
+)]
+pub(crate) fn spawn_password_refresh<F, Fut>(
+    name: &'static str,
+    conn: &DatabaseConnection,
+    interval: Duration,
+    fetch: F,
+) where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<String, ConnectionError>> + Send,
+{
+    let conn = conn.clone();
+    crate::tasks::spawn(name, async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            if crate::managed::is_closed(&conn) {
+                break;
+            }
+            match fetch().await {
+                Ok(password) => set_credentials(&conn, None, &password),
+                Err(e) => log::warn!("Failed to refresh database password: {e}"),
+            }
+        }
+    });
+}
@@ -0,0 +1,89 @@
+use crate::{ConnectionError, DbConnector};
+use sea_orm::DatabaseConnection;
+use std::collections::BTreeMap;
+#[cfg(feature = "config")]
+use std::path::Path;
+use std::sync::Arc;
+
+/// Named connections to several databases, such as `"primary"`,
+/// `"analytics"` and `"audit"`, opened together and shared across the app.
+///
+/// ```ignore
+/// let registry = ConnectionRegistry::from_profiles("db.toml").await?;
+/// let analytics = registry.get("analytics").expect("configured");
+/// ```
+///
+/// Clones share the same pools.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionRegistry {
+    connections: Arc<BTreeMap<String, DatabaseConnection>>,
+    /// The settings each pool was opened with.
+    connectors: Arc<BTreeMap<String, DbConnector>>,
+}
+
+impl ConnectionRegistry {
+    /// Connects every connector, failing with the first error (prefixed
+    /// with the connection's name).
+    pub async fn connect<I, K>(connectors: I) -> Result<Self, ConnectionError>
+    where
+        I: IntoIterator<Item = (K, DbConnector)>,
+        K: Into<String>,
+    {
+        let mut connections = BTreeMap::new();
+        let mut opened = BTreeMap::new();
+        for (name, connector) in connectors {
+            let name = name.into();
+            let conn = connector
+                .clone()
+                .connect()
+                .await
+                .map_err(|e| e.context(&name))?;
+            connections.insert(name.clone(), conn);
+            opened.insert(name, connector);
+        }
+        Ok(Self {
+            connections: Arc::new(connections),
+            connectors: Arc::new(opened),
+        })
+    }
+
+    /// Loads every `[profiles.*]` table of a TOML file, as
+    /// [`DbConnector::load_profiles`] does, and connects them all under
+    /// their profile names.
+    #[cfg(feature = "config")]
+    pub async fn from_profiles<P: AsRef<Path>>(path: P) -> Result<Self, ConnectionError> {
+        Self::connect(DbConnector::load_profiles(path)?).await
+    }
+
+    /// The pool registered under `name`.
+    pub fn get(&self, name: &str) -> Option<DatabaseConnection> {
+        self.connections.get(name).cloned()
+    }
+
+    /// Like [`get`](Self::get), with an error naming the missing
+    /// connection.
+    pub fn require(&self, name: &str) -> Result<DatabaseConnection, ConnectionError> {
+        self.get(name).ok_or_else(|| {
+            ConnectionError::InvalidConfig(format!("No connection named '{name}' is registered"))
+        })
+    }
+
+    /// The settings the pool registered under `name` was opened with.
+    pub fn connector(&self, name: &str) -> Option<&DbConnector> {
+        self.connectors.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.connections.keys().map(String::as_str)
+    }
+
+    /// Closes every pool, for all clones of the registry.
+    pub async fn close(&self) -> Result<(), ConnectionError> {
+        for (name, conn) in self.connections.iter() {
+            conn.close_by_ref()
+                .await
+                .map_err(|e| ConnectionError::DatabaseError(format!("{name}: {e}")))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+//! Hot reload of a connection from a watched configuration file.
+
+use crate::config::{DbConfig, read_profile};
+use crate::{ConnectionError, DbConnector};
+use sea_orm::DatabaseConnection;
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// How often the file is re-read.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A connection that follows edits to a configuration profile.
+///
+/// Created by [`watch_config`]. Call [`connection()`](Self::connection) per
+/// unit of work rather than holding on to the returned handle, so work after
+/// a reload lands on the new pool.
+#[derive(Debug, Clone)]
+pub struct ConfigWatcher {
+    current: watch::Receiver<DatabaseConnection>,
+    config: watch::Receiver<DbConfig>,
+}
+
+impl ConfigWatcher {
+    /// The pool currently in service.
+    pub fn connection(&self) -> DatabaseConnection {
+        self.current.borrow().clone()
+    }
+
+    /// The settings the current pool was opened with.
+    pub fn config(&self) -> DbConfig {
+        self.config.borrow().clone()
+    }
+
+    /// A receiver that is notified each time a reloaded pool is swapped in.
+    pub fn subscribe(&self) -> watch::Receiver<DatabaseConnection> {
+        self.current.clone()
+    }
+}
+
+/// Connects with the `[profiles.<profile>]` table of the TOML file at
+/// `path`, then re-reads the file every 5 seconds.
+///
+/// When the profile's settings change (credentials, host, pool sizes or any
+/// other field), a new pool is opened with them, swapped in, and the old one
+/// drained and closed. A file that fails to parse, or settings that fail to
+/// connect, are logged and the current pool stays in service; the same
+/// broken settings are not retried until the file changes again. Watching
+/// stops when every handle is dropped or the current pool is closed.
+pub async fn watch_config<P: AsRef<Path>>(
+    path: P,
+    profile: &str,
+) -> Result<ConfigWatcher, ConnectionError> {
+    let path = path.as_ref().to_path_buf();
+    let profile = profile.to_string();
+    let config = read_profile(&path, &profile)?;
+    let conn = DbConnector::try_from(config.clone())?.connect().await?;
+    let (tx, current) = watch::channel(conn);
+    let (config_tx, config_rx) = watch::channel(config);
+
+    crate::tasks::spawn("tin-sea-conn::config-watch", async move {
+        let mut rejected: Option<DbConfig> = None;
+        let mut last_error = String::new();
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            if tx.is_closed() || crate::managed::is_closed(&tx.borrow()) {
+                return;
+            }
+            let config = match read_profile(&path, &profile) {
+                Ok(config) => config,
+                Err(e) => {
+                    let message = e.to_string();
+                    if message != last_error {
+                        log::warn!("Keeping the current pool: {message}");
+                        last_error = message;
+                    }
+                    continue;
+                }
+            };
+            last_error.clear();
+            if config == *config_tx.borrow() || rejected.as_ref() == Some(&config) {
+                continue;
+            }
+
+            let changed = changed_fields(&config_tx.borrow(), &config);
+            log::info!(
+                "Profile '{profile}' in {} changed ({}); reconnecting",
+                path.display(),
+                changed.join(", ")
+            );
+            let result = match DbConnector::try_from(config.clone()) {
+                Ok(connector) => connector.connect().await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(conn) => {
+                    rejected = None;
+                    config_tx.send_replace(config);
+                    let old = tx.send_replace(conn);
+                    log::info!("Swapped in a pool with reloaded settings");
+                    if let Err(e) = old.close().await {
+                        log::warn!("Failed to close the pool with old settings: {e}");
+                    }
+                }
+                Err(e) => {
+                    log::warn!("Keeping the current pool; reloaded settings failed: {e}");
+                    rejected = Some(config);
+                }
+            }
+        }
+    });
+
+    Ok(ConfigWatcher {
+        current,
+        config: config_rx,
+    })
+}
+
+/// Names of the settings that differ between `old` and `new`.
+fn changed_fields(old: &DbConfig, new: &DbConfig) -> Vec<String> {
+    let table = |config: &DbConfig| match toml::Value::try_from(config) {
+        Ok(toml::Value::Table(table)) => table,
+        _ => Default::default(),
+    };
+    let (old, new) = (table(old), table(new));
+    let mut keys: Vec<String> = old
+        .keys()
+        .chain(new.keys())
+        .filter(|key| old.get(*key) != new.get(*key))
+        .cloned()
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
@@ -0,0 +1,128 @@
+use crate::{ErrorClass, classify};
+use sea_orm::{DatabaseConnection, DatabaseTransaction, DbErr, TransactionTrait};
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::BuildHasher;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// How often and how patiently [`retry_transaction`] re-runs a transaction.
+///
+/// Defaults to 5 attempts with exponential backoff from 10ms up to 1s. Each
+/// wait is jittered to between half and all of the backoff, so transactions
+/// that conflicted do not collide again in lockstep.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs in total, including the first. At least 1.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+}
+
+/// Runs `f` in a transaction and commits it, starting over in a new
+/// transaction when the database aborted it as a serialization failure
+/// (Postgres `40001`, as CockroachDB and `SERIALIZABLE` isolation report
+/// conflicts) or deadlock victim (Postgres `40P01`, MySQL `1213`), or as a
+/// TiDB write conflict (`9007`), i.e. whenever [`classify`] reports
+/// [`ErrorClass::Conflict`].
+///
+/// `f` may run several times, so it should not have side effects outside
+/// the transaction. Any other error, or a retryable one on the last
+/// attempt, rolls back and is returned as is.
+///
+/// ```ignore
+/// let id = retry_transaction(&db, &RetryPolicy::default(), |txn| {
+///     Box::pin(async move {
+///         let account = Account::find_by_id(1).one(txn).await?;
+///         // ...
+///         Ok(account.map(|a| a.id))
+///     })
+/// })
+/// .await?;
+/// ```
+pub async fn retry_transaction<F, T>(
+    conn: &DatabaseConnection,
+    policy: &RetryPolicy,
+    mut f: F,
+) -> Result<T, DbErr>
+where
+    F: for<'c> FnMut(
+        &'c DatabaseTransaction,
+    ) -> Pin<Box<dyn Future<Output = Result<T, DbErr>> + Send + 'c>>,
+{
+    let jitter = RandomState::new();
+    let mut backoff = policy.initial_backoff;
+    let mut attempt = 1;
+    loop {
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!(
+            "transaction_attempt",
+            attempt,
+            error.class = tracing::field::Empty,
+        );
+        let fut = async {
+            let txn = conn.begin().await?;
+            match f(&txn).await {
+                Ok(value) => txn.commit().await.map(|()| value),
+                Err(e) => {
+                    // The transaction is already aborted on a conflict, so a
+                    // failed rollback tells nothing new.
+                    let _ = txn.rollback().await;
+                    Err(e)
+                }
+            }
+        };
+        #[cfg(feature = "tracing")]
+        let fut = tracing::Instrument::instrument(fut, span.clone());
+        let result = fut.await;
+        #[cfg(feature = "tracing")]
+        if let Err(e) = &result {
+            span.record("error.class", tracing::field::debug(classify(e)));
+        }
+        match result {
+            Err(e) if attempt < policy.max_attempts && classify(&e) == ErrorClass::Conflict => {
+                let wait = backoff / 2
+                    + backoff.mul_f64((jitter.hash_one(attempt) % 1000) as f64 / 2000.0);
+                log::debug!(
+                    "Transaction attempt {attempt} of {} conflicted, retrying in {wait:?}: {e}",
+                    policy.max_attempts
+                );
+                tokio::time::sleep(wait).await;
+                backoff = (backoff * 2).min(policy.max_backoff);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
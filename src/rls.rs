@@ -0,0 +1,73 @@
+use crate::ConnectionError;
+use sea_orm::{
+    ConnectionTrait, DatabaseConnection, DatabaseTransaction, DbBackend, Statement,
+    TransactionTrait,
+};
+
+/// Setting that row-level security policies read the tenant from, unless
+/// changed with [`TenantScope::setting`].
+const DEFAULT_SETTING: &str = "app.current_tenant";
+
+/// Scopes work on a Postgres connection to one tenant, for row-level
+/// security policies such as
+/// `USING (tenant_id = current_setting('app.current_tenant'))`.
+///
+/// ```ignore
+/// let txn = with_tenant(&db, "acme").begin().await?;
+/// let orders = Order::find().all(&txn).await?;
+/// txn.commit().await?;
+/// ```
+pub fn with_tenant(conn: &DatabaseConnection, tenant_id: impl Into<String>) -> TenantScope {
+    TenantScope {
+        conn: conn.clone(),
+        tenant_id: tenant_id.into(),
+        setting: DEFAULT_SETTING.into(),
+    }
+}
+
+/// A connection bound to a tenant, from [`with_tenant`].
+#[derive(Debug, Clone)]
+pub struct TenantScope {
+    conn: DatabaseConnection,
+    tenant_id: String,
+    setting: String,
+}
+
+impl TenantScope {
+    /// The setting the tenant id is stored in. Defaults to
+    /// `app.current_tenant`.
+    pub fn setting<S: Into<String>>(mut self, name: S) -> Self {
+        self.setting = name.into();
+        self
+    }
+
+    pub fn tenant_id(&self) -> &str {
+        &self.tenant_id
+    }
+
+    /// Starts a transaction with the tenant set for its duration only
+    /// (`SET LOCAL`), so the pooled connection carries nothing over to its
+    /// next user. Queries outside such a transaction see no tenant.
+    pub async fn begin(&self) -> Result<DatabaseTransaction, ConnectionError> {
+        if self.conn.get_database_backend() != DbBackend::Postgres {
+            return Err(ConnectionError::InvalidConfig(
+                "with_tenant requires a PostgreSQL connection".into(),
+            ));
+        }
+        let txn = self
+            .conn
+            .begin()
+            .await
+            .map_err(|e| ConnectionError::DatabaseError(e.to_string()))?;
+        txn.execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            "SELECT set_config($1, $2, true)",
+            [self.setting.clone().into(), self.tenant_id.clone().into()],
+        ))
+        .await
+        .map_err(|e| {
+            ConnectionError::DatabaseError(format!("Failed to set {}: {e}", self.setting))
+        })?;
+        Ok(txn)
+    }
+}
@@ -0,0 +1,79 @@
+use crate::{ConnectionError, Credentials, DbConnector};
+use sea_orm::DatabaseConnection;
+use tokio::sync::watch;
+
+/// A connection that follows credential rotations without auth failures.
+///
+/// Every time new [`Credentials`] are published on the channel given to
+/// [`RotatingCredentials::connect`], a new pool is opened with them. Only
+/// once it has connected (and passed the connector's post-connect checks)
+/// is it swapped in; the old pool is then drained: connections in use finish
+/// their work, after which it is closed. If the new pool cannot connect, the
+/// old one stays in service and the failure is logged.
+///
+/// Call [`connection()`](Self::connection) per unit of work rather than
+/// holding on to the returned handle, so work after a swap lands on the new
+/// pool.
+#[derive(Debug, Clone)]
+pub struct RotatingCredentials {
+    current: watch::Receiver<DatabaseConnection>,
+}
+
+impl RotatingCredentials {
+    /// Connects with the credentials currently in `updates` and starts
+    /// following the channel. Rotation stops when the sender is dropped.
+    pub async fn connect(
+        connector: DbConnector,
+        mut updates: watch::Receiver<Credentials>,
+    ) -> Result<Self, ConnectionError> {
+        let credentials = updates.borrow_and_update().clone();
+        let conn = Self::open(&connector, credentials).await?;
+        let (tx, current) = watch::channel(conn);
+
+        crate::tasks::spawn("tin-sea-conn::credential-rotation", async move {
+            while updates.changed().await.is_ok() {
+                let credentials = updates.borrow_and_update().clone();
+                match Self::open(&connector, credentials).await {
+                    Ok(conn) => {
+                        let old = tx.send_replace(conn);
+                        log::info!("Swapped in a pool with rotated database credentials");
+                        // Waits for checked-out connections to be returned.
+                        if let Err(e) = old.close().await {
+                            log::warn!("Failed to close the pool with old credentials: {e}");
+                        }
+                    }
+                    Err(e) => log::warn!(
+                        "Keeping the current pool; connecting with rotated credentials failed: {e}"
+                    ),
+                }
+                if tx.is_closed() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self { current })
+    }
+
+    async fn open(
+        connector: &DbConnector,
+        credentials: Credentials,
+    ) -> Result<DatabaseConnection, ConnectionError> {
+        connector
+            .clone()
+            .username(credentials.username)
+            .password(credentials.password)
+            .connect()
+            .await
+    }
+
+    /// The pool currently in service.
+    pub fn connection(&self) -> DatabaseConnection {
+        self.current.borrow().clone()
+    }
+
+    /// A receiver that is notified each time a new pool is swapped in.
+    pub fn subscribe(&self) -> watch::Receiver<DatabaseConnection> {
+        self.current.clone()
+    }
+}
@@ -0,0 +1,141 @@
+//! Warnings when a pool runs short of connections.
+
+use crate::PoolStats;
+use sea_orm::DatabaseConnection;
+use std::time::{Duration, Instant};
+
+/// How often the pool is sampled.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+/// While the condition lasts, its warning is repeated at most this often.
+const REPEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Thresholds {
+    /// Percentage of `max_connections` in use.
+    pub utilization: Option<u8>,
+    pub acquire_wait: Option<Duration>,
+}
+
+/// A warning that is logged when its condition starts, repeated while it
+/// lasts, and followed by a note once it clears.
+#[derive(Default)]
+struct Alarm {
+    raised_at: Option<Instant>,
+}
+
+impl Alarm {
+    /// Whether to log the warning now.
+    fn raise(&mut self) -> bool {
+        let due = self
+            .raised_at
+            .is_none_or(|at| at.elapsed() >= REPEAT_INTERVAL);
+        if due {
+            self.raised_at = Some(Instant::now());
+        }
+        due
+    }
+
+    /// Whether the condition just cleared.
+    fn clear(&mut self) -> bool {
+        self.raised_at.take().is_some()
+    }
+}
+
+/// Samples the pool behind `conn` every second and logs a warning with its
+/// stats when either threshold is crossed. Ends once the pool is closed.
+pub(crate) fn spawn(conn: &DatabaseConnection, thresholds: Thresholds) {
+    let conn = conn.clone();
+    crate::tasks::spawn("tin-sea-conn::pool-saturation", async move {
+        let mut busy = Alarm::default();
+        let mut waiting = Alarm::default();
+        loop {
+            tokio::time::sleep(SAMPLE_INTERVAL).await;
+            if crate::managed::is_closed(&conn) {
+                break;
+            }
+            let stats = PoolStats::of(&conn);
+
+            if let Some(percent) = thresholds.utilization {
+                let used = percent_in_use(&stats);
+                if used >= u32::from(percent) {
+                    if busy.raise() {
+                        log::warn!(
+                            "Connection pool is {used}% in use, at or above the {percent}% threshold ({})",
+                            describe(&stats)
+                        );
+                    }
+                } else if busy.clear() {
+                    log::info!(
+                        "Connection pool is back below {percent}% in use ({})",
+                        describe(&stats)
+                    );
+                }
+            }
+
+            // Callers only wait once every connection is open and busy.
+            // Then queue up behind them and see how long it takes.
+            if let Some(limit) = thresholds.acquire_wait {
+                if stats.is_full() && acquire_wait(&conn, limit).await.is_none() {
+                    if waiting.raise() {
+                        log::warn!(
+                            "Waiting for a pooled connection takes longer than {limit:?} ({})",
+                            describe(&PoolStats::of(&conn))
+                        );
+                    }
+                } else if waiting.clear() {
+                    log::info!(
+                        "Pooled connections are available within {limit:?} again ({})",
+                        describe(&stats)
+                    );
+                }
+            }
+        }
+    });
+}
+
+fn percent_in_use(stats: &PoolStats) -> u32 {
+    if stats.max_connections == 0 {
+        return 0;
+    }
+    stats.in_use() * 100 / stats.max_connections
+}
+
+fn describe(stats: &PoolStats) -> String {
+    format!(
+        "{} in use, {} idle, {} max",
+        stats.in_use(),
+        stats.idle,
+        stats.max_connections
+    )
+}
+
+/// How long checking out a connection took, or `None` if it took longer
+/// than `limit`. The connection is handed straight back.
+pub(crate) async fn acquire_wait(conn: &DatabaseConnection, limit: Duration) -> Option<Duration> {
+    async fn acquire<DB: sea_orm::sqlx::Database>(
+        pool: &sea_orm::sqlx::Pool<DB>,
+        limit: Duration,
+    ) -> Option<Duration> {
+        let started = Instant::now();
+        // A pool error (e.g. closed) is not a slow acquire.
+        tokio::time::timeout(limit, pool.acquire())
+            .await
+            .ok()
+            .map(|_| started.elapsed())
+    }
+    match conn {
+        #[cfg(feature = "postgres")]
+        DatabaseConnection::SqlxPostgresPoolConnection(_) => {
+            acquire(conn.get_postgres_connection_pool(), limit).await
+        }
+        #[cfg(feature = "mysql")]
+        DatabaseConnection::SqlxMySqlPoolConnection(_) => {
+            acquire(conn.get_mysql_connection_pool(), limit).await
+        }
+        #[cfg(feature = "sqlite")]
+        DatabaseConnection::SqlxSqlitePoolConnection(_) => {
+            acquire(conn.get_sqlite_connection_pool(), limit).await
+        }
+        _ => Some(Duration::ZERO),
+    }
+}
@@ -0,0 +1,58 @@
+//! Seeding a database from a directory of SQL scripts after connecting.
+
+use crate::ConnectionError;
+use sea_orm::{ConnectionTrait, DatabaseConnection};
+use std::path::{Path, PathBuf};
+
+/// The `.sql` files directly in `dir`, in lexical order of their names.
+fn sql_files(dir: &Path) -> Result<Vec<PathBuf>, ConnectionError> {
+    let entries = std::fs::read_dir(dir).map_err(|e| {
+        ConnectionError::InvalidConfig(format!(
+            "Failed to read seed directory {}: {e}",
+            dir.display()
+        ))
+    })?;
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|e| {
+                ConnectionError::InvalidConfig(format!(
+                    "Failed to read seed directory {}: {e}",
+                    dir.display()
+                ))
+            })?
+            .path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "sql") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Runs every `.sql` file in `dir`, each as a single unprepared batch, so
+/// a file may hold several statements. Stops at the first failing file.
+pub(crate) async fn run_dir(conn: &DatabaseConnection, dir: &Path) -> Result<(), ConnectionError> {
+    let files = sql_files(dir)?;
+    for file in &files {
+        let sql = std::fs::read_to_string(file).map_err(|e| {
+            ConnectionError::InvalidConfig(format!(
+                "Failed to read seed file {}: {e}",
+                file.display()
+            ))
+        })?;
+        conn.execute_unprepared(&sql).await.map_err(|e| {
+            ConnectionError::DatabaseError(format!("Seed file {} failed: {e}", file.display()))
+        })?;
+        log::debug!("Ran seed file {}", file.display());
+    }
+    log::info!("Ran {} seed file(s) from {}", files.len(), dir.display());
+    Ok(())
+}
+
+/// Whether SQLite will create `path` on connect: it is missing, empty (as
+/// left by [`reset_database`](crate::reset_database)), or in memory.
+#[cfg(feature = "sqlite")]
+pub(crate) fn is_new_sqlite_file(path: &str) -> bool {
+    path == ":memory:" || std::fs::metadata(path).map_or(true, |meta| meta.len() == 0)
+}
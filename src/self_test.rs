@@ -0,0 +1,172 @@
+use crate::{ConnectionError, DbConnector};
+use sea_orm::DatabaseConnection;
+#[cfg(any(feature = "postgres", feature = "mysql", feature = "sqlcipher"))]
+use sea_orm::{ConnectionTrait, DbBackend, Statement};
+
+/// Runs the post-connect assertions configured on the builder.
+#[cfg_attr(
+    not(any(feature = "postgres", feature = "mysql", feature = "sqlcipher")),
+    allow(unused_variables)
+)]
+pub(crate) async fn run(
+    connector: &DbConnector,
+    conn: &DatabaseConnection,
+) -> Result<(), ConnectionError> {
+    #[cfg(feature = "mysql")]
+    if let Some(expected) = &connector.mysql_collation {
+        assert_mysql_collation(conn, expected).await?;
+    }
+
+    #[cfg(feature = "mysql")]
+    if connector.db_type() == Some(&crate::DatabaseType::MariaDB) {
+        check_mariadb(conn).await;
+    }
+
+    #[cfg(feature = "postgres")]
+    if let Some(setup) = connector.timescaledb {
+        ensure_timescaledb(conn, setup).await?;
+    }
+
+    #[cfg(feature = "postgres")]
+    if let Some(schema) = &connector.ensure_schema {
+        crate::bootstrap::ensure_schema(conn, schema).await?;
+    }
+
+    #[cfg(feature = "sqlcipher")]
+    if connector.sqlite_encryption_key.is_some() {
+        check_sqlcipher_key(conn).await?;
+    }
+
+    Ok(())
+}
+
+/// Checks that the server, database and connection collations all match `expected`.
+///
+/// A mismatch between these three silently changes how string comparisons,
+/// `ORDER BY` and unique indexes behave depending on where a literal came from.
+#[cfg(feature = "mysql")]
+async fn assert_mysql_collation(
+    conn: &DatabaseConnection,
+    expected: &str,
+) -> Result<(), ConnectionError> {
+    let row = conn
+        .query_one(Statement::from_string(
+            DbBackend::MySql,
+            "SELECT @@collation_server, @@collation_database, @@collation_connection",
+        ))
+        .await
+        .map_err(|e| ConnectionError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| ConnectionError::DatabaseError("Collation query returned no rows".into()))?;
+
+    let names = [
+        "collation_server",
+        "collation_database",
+        "collation_connection",
+    ];
+    let mut mismatches = Vec::new();
+    for (idx, name) in names.iter().enumerate() {
+        let actual: String = row
+            .try_get_by_index(idx)
+            .map_err(|e| ConnectionError::DatabaseError(e.to_string()))?;
+        if actual != expected {
+            mismatches.push(format!("{name}={actual}"));
+        }
+    }
+
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(ConnectionError::DatabaseError(format!(
+            "Collation mismatch (expected {expected}): {}",
+            mismatches.join(", ")
+        )))
+    }
+}
+
+/// Warns if a connector set up for MariaDB reached a server that does not
+/// report itself as one, e.g. MySQL behind a mislabelled endpoint.
+#[cfg(feature = "mysql")]
+async fn check_mariadb(conn: &DatabaseConnection) {
+    let version = conn
+        .query_one(Statement::from_string(DbBackend::MySql, "SELECT VERSION()"))
+        .await
+        .ok()
+        .flatten()
+        .and_then(|row| row.try_get_by_index::<String>(0).ok());
+    match version {
+        Some(version) if !version.contains("MariaDB") => log::warn!(
+            "Connected with mariadb() but the server reports version {version}; use mysql() for MySQL"
+        ),
+        Some(_) => {}
+        None => log::debug!("Could not read the server version to check for MariaDB"),
+    }
+}
+
+/// Checks that the TimescaleDB extension is installed in the database,
+/// installing it first when `setup` says so.
+#[cfg(feature = "postgres")]
+async fn ensure_timescaledb(
+    conn: &DatabaseConnection,
+    setup: crate::connection::TimescaleSetup,
+) -> Result<(), ConnectionError> {
+    let row = conn
+        .query_one(Statement::from_string(
+            DbBackend::Postgres,
+            "SELECT (SELECT extversion FROM pg_extension WHERE extname = 'timescaledb'), \
+             EXISTS (SELECT 1 FROM pg_available_extensions WHERE name = 'timescaledb')",
+        ))
+        .await
+        .map_err(|e| ConnectionError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| ConnectionError::DatabaseError("Extension query returned no rows".into()))?;
+    let installed: Option<String> = row
+        .try_get_by_index(0)
+        .map_err(|e| ConnectionError::DatabaseError(e.to_string()))?;
+    let available: bool = row
+        .try_get_by_index(1)
+        .map_err(|e| ConnectionError::DatabaseError(e.to_string()))?;
+
+    if let Some(version) = installed {
+        log::debug!("TimescaleDB {version} is installed");
+        return Ok(());
+    }
+    if !available {
+        return Err(ConnectionError::DatabaseError(
+            "The timescaledb extension is not available on this server; \
+             TimescaleDB must be installed and listed in shared_preload_libraries"
+                .into(),
+        ));
+    }
+    match setup {
+        crate::connection::TimescaleSetup::Verify => Err(ConnectionError::DatabaseError(
+            "The timescaledb extension is not installed in this database; \
+             run CREATE EXTENSION timescaledb or use create_timescale_extension()"
+                .into(),
+        )),
+        crate::connection::TimescaleSetup::Create => {
+            conn.execute_unprepared("CREATE EXTENSION IF NOT EXISTS timescaledb")
+                .await
+                .map_err(|e| {
+                    ConnectionError::DatabaseError(format!(
+                        "Creating the timescaledb extension failed: {e}"
+                    ))
+                })?;
+            log::info!("Created the timescaledb extension");
+            Ok(())
+        }
+    }
+}
+
+/// SQLCipher only decrypts on first access, so a wrong key would otherwise go
+/// unnoticed until the first query.
+#[cfg(feature = "sqlcipher")]
+async fn check_sqlcipher_key(conn: &DatabaseConnection) -> Result<(), ConnectionError> {
+    conn.query_one(Statement::from_string(
+        DbBackend::Sqlite,
+        "SELECT count(*) FROM sqlite_master",
+    ))
+    .await
+    .map(|_| ())
+    .map_err(|e| {
+        ConnectionError::ConnectionFailed(format!("Failed to open encrypted database: {e}"))
+    })
+}
@@ -0,0 +1,45 @@
+use crate::ConnectionError;
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, Statement};
+
+/// What a server reports about a fresh session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SessionState {
+    /// The server is a replica: a Postgres standby in recovery, or a MySQL
+    /// server with `read_only` set.
+    pub in_recovery: bool,
+    /// New transactions are read-only by default.
+    pub read_only: bool,
+}
+
+pub(crate) async fn probe(conn: &DatabaseConnection) -> Result<SessionState, ConnectionError> {
+    let backend = conn.get_database_backend();
+    let sql = match backend {
+        DbBackend::Postgres => {
+            "SELECT pg_is_in_recovery(), current_setting('transaction_read_only') = 'on'"
+        }
+        DbBackend::MySql => {
+            "SELECT CAST(@@global.read_only AS SIGNED), CAST(@@session.transaction_read_only AS SIGNED)"
+        }
+        DbBackend::Sqlite => {
+            return Err(ConnectionError::InvalidConfig(
+                "SQLite has no primary or replica role".into(),
+            ));
+        }
+    };
+    let row = conn
+        .query_one(Statement::from_string(backend, sql))
+        .await
+        .map_err(|e| ConnectionError::DatabaseError(e.to_string()))?
+        .ok_or_else(|| ConnectionError::DatabaseError("Session query returned no rows".into()))?;
+    let get = |idx| {
+        match backend {
+            DbBackend::MySql => row.try_get_by_index::<i64>(idx).map(|v| v != 0),
+            _ => row.try_get_by_index::<bool>(idx),
+        }
+        .map_err(|e| ConnectionError::DatabaseError(e.to_string()))
+    };
+    Ok(SessionState {
+        in_recovery: get(0)?,
+        read_only: get(1)?,
+    })
+}
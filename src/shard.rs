@@ -0,0 +1,619 @@
+use crate::{ConnectionError, DbConnector};
+use sea_orm::DatabaseConnection;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+/// Points each shard gets on the consistent-hash ring. More points spread
+/// keys more evenly at the cost of a larger ring.
+const VIRTUAL_NODES: usize = 128;
+/// Default limit on shards [`ShardedConnector::for_each_shard`] works on at
+/// once.
+const DEFAULT_PARALLELISM: usize = 8;
+
+type HashFn = dyn Fn(&[u8]) -> u64 + Send + Sync;
+
+struct Shard {
+    name: String,
+    conn: DatabaseConnection,
+}
+
+struct Layout {
+    shards: Vec<Shard>,
+    /// Sorted `(point, shard index)` pairs, or `None` to route by modulo.
+    ring: Option<Vec<(u64, usize)>>,
+}
+
+/// Pools for a sharded database, routing each key to its shard.
+///
+/// With [`connect`](Self::connect) a key goes to shard
+/// `hash(key) % shards`. With [`connect_ring`](Self::connect_ring) shards
+/// are named and placed on a consistent-hash ring, so shards can be added
+/// and removed at runtime while moving only the keys of the shard that
+/// changed.
+///
+/// The hash function is supplied by the caller because it decides where
+/// data lives: it must give the same result in every process and release,
+/// which rules out `std`'s `DefaultHasher`.
+///
+/// ```ignore
+/// let shards = ShardedConnector::connect([shard0, shard1, shard2], |key| {
+///     crc32fast::hash(key) as u64
+/// })
+/// .await?;
+/// let db = shards.shard_for(tenant_id);
+/// ```
+///
+/// Clones share the same pools. Call [`shard_for`](Self::shard_for) per
+/// unit of work rather than holding on to the returned handle, so work
+/// after a reconfiguration lands on the right shard.
+#[derive(Clone)]
+pub struct ShardedConnector {
+    layout: Arc<watch::Sender<Layout>>,
+    hash: Arc<HashFn>,
+    parallelism: usize,
+}
+
+impl fmt::Debug for ShardedConnector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let layout = self.layout.borrow();
+        f.debug_struct("ShardedConnector")
+            .field(
+                "shards",
+                &layout.shards.iter().map(|s| &s.name).collect::<Vec<_>>(),
+            )
+            .field("consistent_hashing", &layout.ring.is_some())
+            .field("parallelism", &self.parallelism)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ShardedConnector {
+    /// Connects every shard, failing with the first error. Shards are
+    /// numbered in the order given.
+    pub async fn connect<I, F>(shards: I, hash: F) -> Result<Self, ConnectionError>
+    where
+        I: IntoIterator<Item = DbConnector>,
+        F: Fn(&[u8]) -> u64 + Send + Sync + 'static,
+    {
+        let shards = shards
+            .into_iter()
+            .enumerate()
+            .map(|(idx, connector)| (idx.to_string(), connector));
+        Self::open(shards, Arc::new(hash), false).await
+    }
+
+    /// Connects every named shard, failing with the first error, and routes
+    /// keys with a consistent-hash ring. The names place the shards on the
+    /// ring, so keep them stable across restarts.
+    pub async fn connect_ring<I, K, F>(shards: I, hash: F) -> Result<Self, ConnectionError>
+    where
+        I: IntoIterator<Item = (K, DbConnector)>,
+        K: Into<String>,
+        F: Fn(&[u8]) -> u64 + Send + Sync + 'static,
+    {
+        let shards = shards
+            .into_iter()
+            .map(|(name, connector)| (name.into(), connector));
+        Self::open(shards, Arc::new(hash), true).await
+    }
+
+    async fn open(
+        shards: impl Iterator<Item = (String, DbConnector)>,
+        hash: Arc<HashFn>,
+        ring: bool,
+    ) -> Result<Self, ConnectionError> {
+        let shards: Vec<(String, DbConnector)> = shards.collect();
+        if shards.is_empty() {
+            return Err(ConnectionError::InvalidConfig(
+                "ShardedConnector needs at least one shard".into(),
+            ));
+        }
+        for (idx, (name, _)) in shards.iter().enumerate() {
+            if shards[..idx].iter().any(|(other, _)| other == name) {
+                return Err(ConnectionError::InvalidConfig(format!(
+                    "Shard '{name}' is listed twice"
+                )));
+            }
+        }
+
+        let mut connected: Vec<Shard> = Vec::with_capacity(shards.len());
+        for (name, connector) in shards {
+            match connector.connect().await {
+                Ok(conn) => connected.push(Shard { name, conn }),
+                Err(e) => {
+                    // Don't leave the shards opened so far running.
+                    for shard in connected {
+                        let _ = shard.conn.close().await;
+                    }
+                    return Err(e.context(&format!("shard {name}")));
+                }
+            }
+        }
+        let ring = ring.then(|| build_ring(&connected, hash.as_ref()));
+        Ok(Self {
+            layout: Arc::new(watch::Sender::new(Layout {
+                shards: connected,
+                ring,
+            })),
+            hash,
+            parallelism: DEFAULT_PARALLELISM,
+        })
+    }
+
+    /// Maximum number of shards [`for_each_shard`](Self::for_each_shard)
+    /// works on at once. Defaults to 8.
+    pub fn parallelism(mut self, max: usize) -> Self {
+        self.parallelism = max.max(1);
+        self
+    }
+
+    /// The position, among [`shards()`](Self::shards), of the shard `key`
+    /// belongs to.
+    pub fn shard_index(&self, key: impl AsRef<[u8]>) -> usize {
+        let layout = self.layout.borrow();
+        self.route(&layout, key.as_ref())
+    }
+
+    fn route(&self, layout: &Layout, key: &[u8]) -> usize {
+        let hash = (self.hash)(key);
+        match &layout.ring {
+            Some(ring) => {
+                let point = mix(hash);
+                let pos = ring.partition_point(|&(p, _)| p < point);
+                ring[pos % ring.len()].1
+            }
+            None => (hash % layout.shards.len() as u64) as usize,
+        }
+    }
+
+    /// The name of the shard `key` belongs to; its number for shards opened
+    /// with [`connect`](Self::connect).
+    pub fn shard_name(&self, key: impl AsRef<[u8]>) -> String {
+        let layout = self.layout.borrow();
+        layout.shards[self.route(&layout, key.as_ref())]
+            .name
+            .clone()
+    }
+
+    /// The pool of the shard `key` belongs to.
+    pub fn shard_for(&self, key: impl AsRef<[u8]>) -> DatabaseConnection {
+        let layout = self.layout.borrow();
+        layout.shards[self.route(&layout, key.as_ref())]
+            .conn
+            .clone()
+    }
+
+    pub fn shards(&self) -> Vec<DatabaseConnection> {
+        let layout = self.layout.borrow();
+        layout
+            .shards
+            .iter()
+            .map(|shard| shard.conn.clone())
+            .collect()
+    }
+
+    /// Runs `f` against every shard concurrently, at most
+    /// [`parallelism`](Self::parallelism) at a time, and returns each
+    /// shard's name with its result, in shard order. A failing shard does
+    /// not stop the others. A shard whose task was cancelled, e.g. by the
+    /// runtime shutting down, gets a [`ConnectionError`] converted into `E`.
+    ///
+    /// ```ignore
+    /// let results = shards
+    ///     .for_each_shard(|db| async move {
+    ///         db.execute_unprepared("VACUUM ANALYZE").await
+    ///     })
+    ///     .await;
+    /// for (shard, result) in results {
+    ///     if let Err(e) = result {
+    ///         log::error!("shard {shard}: {e}");
+    ///     }
+    /// }
+    /// ```
+    pub async fn for_each_shard<F, Fut, T, E>(&self, f: F) -> Vec<(String, Result<T, E>)>
+    where
+        F: Fn(DatabaseConnection) -> Fut,
+        Fut: Future<Output = Result<T, E>> + Send + 'static,
+        T: Send + 'static,
+        E: From<ConnectionError> + Send + 'static,
+    {
+        let shards: Vec<(String, DatabaseConnection)> = self
+            .layout
+            .borrow()
+            .shards
+            .iter()
+            .map(|shard| (shard.name.clone(), shard.conn.clone()))
+            .collect();
+        let mut results: Vec<Option<Result<T, E>>> = shards.iter().map(|_| None).collect();
+
+        let mut pending = shards.iter().enumerate();
+        let mut tasks = JoinSet::new();
+        let mut spawned = std::collections::HashMap::new();
+        loop {
+            while tasks.len() < self.parallelism {
+                let Some((idx, (_, conn))) = pending.next() else {
+                    break;
+                };
+                let work = f(conn.clone());
+                let task = tasks.spawn(async move { (idx, work.await) });
+                spawned.insert(task.id(), idx);
+            }
+            let Some(joined) = tasks.join_next().await else {
+                break;
+            };
+            match joined {
+                Ok((idx, result)) => results[idx] = Some(result),
+                Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+                Err(e) => {
+                    let idx = spawned[&e.id()];
+                    let name = &shards[idx].0;
+                    results[idx] = Some(Err(ConnectionError::ConnectionFailed(format!(
+                        "shard {name}: {e}"
+                    ))
+                    .into()));
+                }
+            }
+        }
+
+        shards
+            .into_iter()
+            .zip(results)
+            .filter_map(|((name, _), result)| Some((name, result?)))
+            .collect()
+    }
+
+    /// Connects a new shard and puts it on the ring. Keys that now map to
+    /// it move there from the shards next to it; no other keys move.
+    /// Copying their data over is up to the caller.
+    ///
+    /// Only for shards opened with [`connect_ring`](Self::connect_ring).
+    pub async fn add_shard<K: Into<String>>(
+        &self,
+        name: K,
+        connector: DbConnector,
+    ) -> Result<(), ConnectionError> {
+        let name = name.into();
+        self.ensure_ring()?;
+        let conn = connector
+            .connect()
+            .await
+            .map_err(|e| e.context(&format!("shard {name}")))?;
+
+        let mut added = false;
+        self.layout.send_if_modified(|layout| {
+            if layout.shards.iter().any(|shard| shard.name == name) {
+                return false;
+            }
+            layout.shards.push(Shard {
+                name: name.clone(),
+                conn: conn.clone(),
+            });
+            layout.ring = Some(build_ring(&layout.shards, self.hash.as_ref()));
+            added = true;
+            true
+        });
+        if !added {
+            let _ = conn.close().await;
+            return Err(ConnectionError::InvalidConfig(format!(
+                "Shard '{name}' already exists"
+            )));
+        }
+        log::info!("Added shard '{name}' to the ring");
+        Ok(())
+    }
+
+    /// Takes a shard off the ring, so its keys move to the shards next to
+    /// it, and then closes its pool once in-flight work on it finishes.
+    ///
+    /// Only for shards opened with [`connect_ring`](Self::connect_ring).
+    pub async fn remove_shard(&self, name: &str) -> Result<(), ConnectionError> {
+        self.ensure_ring()?;
+        let mut removed = None;
+        self.layout.send_if_modified(|layout| {
+            let Some(idx) = layout.shards.iter().position(|shard| shard.name == name) else {
+                return false;
+            };
+            if layout.shards.len() == 1 {
+                return false;
+            }
+            removed = Some(layout.shards.remove(idx));
+            layout.ring = Some(build_ring(&layout.shards, self.hash.as_ref()));
+            true
+        });
+        let Some(shard) = removed else {
+            return Err(ConnectionError::InvalidConfig(format!(
+                "No shard '{name}' to remove, or it is the last one"
+            )));
+        };
+        log::info!("Removed shard '{name}' from the ring, draining its pool");
+        shard
+            .conn
+            .close()
+            .await
+            .map_err(|e| ConnectionError::DatabaseError(format!("shard {name}: {e}")))
+    }
+
+    fn ensure_ring(&self) -> Result<(), ConnectionError> {
+        if self.layout.borrow().ring.is_none() {
+            return Err(ConnectionError::InvalidConfig(
+                "Shards can only be added or removed with ShardedConnector::connect_ring()".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Closes every shard's pool, for all clones.
+    pub async fn close(&self) -> Result<(), ConnectionError> {
+        let shards: Vec<(String, DatabaseConnection)> = self
+            .layout
+            .borrow()
+            .shards
+            .iter()
+            .map(|shard| (shard.name.clone(), shard.conn.clone()))
+            .collect();
+        for (name, conn) in shards {
+            conn.close()
+                .await
+                .map_err(|e| ConnectionError::DatabaseError(format!("shard {name}: {e}")))?;
+        }
+        Ok(())
+    }
+}
+
+fn build_ring(shards: &[Shard], hash: &HashFn) -> Vec<(u64, usize)> {
+    let mut ring: Vec<(u64, usize)> = shards
+        .iter()
+        .enumerate()
+        .flat_map(|(idx, shard)| {
+            (0..VIRTUAL_NODES).map(move |vnode| {
+                let point = hash(format!("{}#{vnode}", shard.name).as_bytes());
+                (mix(point), idx)
+            })
+        })
+        .collect();
+    ring.sort_unstable();
+    ring
+}
+
+/// Spreads hashes over the whole ring. Hashes such as FNV differ only in a
+/// few bits for similar inputs like `shard#1` and `shard#2`, which would
+/// bunch a shard's points together. This is the splitmix64 finalizer.
+fn mix(mut hash: u64) -> u64 {
+    hash ^= hash >> 30;
+    hash = hash.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash ^= hash >> 27;
+    hash = hash.wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DEFAULT_PARALLELISM, HashFn, Layout, Shard, ShardedConnector, VIRTUAL_NODES, build_ring,
+        mix,
+    };
+    use sea_orm::DatabaseConnection;
+    use std::sync::Arc;
+    use tokio::sync::watch;
+
+    /// 64-bit FNV-1a, stable across processes as routing requires.
+    fn fnv1a(bytes: &[u8]) -> u64 {
+        bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    fn shards(names: &[&str]) -> Vec<Shard> {
+        names
+            .iter()
+            .map(|name| Shard {
+                name: name.to_string(),
+                conn: DatabaseConnection::Disconnected,
+            })
+            .collect()
+    }
+
+    fn sharded(names: &[&str], ring: bool) -> ShardedConnector {
+        let shards = shards(names);
+        let hash: Arc<HashFn> = Arc::new(fnv1a);
+        let ring = ring.then(|| build_ring(&shards, hash.as_ref()));
+        ShardedConnector {
+            layout: Arc::new(watch::Sender::new(Layout { shards, ring })),
+            hash,
+            parallelism: DEFAULT_PARALLELISM,
+        }
+    }
+
+    fn keys() -> impl Iterator<Item = String> {
+        (0..10_000).map(|i| format!("tenant-{i}"))
+    }
+
+    fn routes(sharded: &ShardedConnector) -> Vec<String> {
+        keys().map(|key| sharded.shard_name(key)).collect()
+    }
+
+    #[test]
+    fn ring_has_every_virtual_node_in_order() {
+        let ring = build_ring(&shards(&["a", "b", "c"]), &fnv1a);
+        assert_eq!(ring.len(), 3 * VIRTUAL_NODES);
+        assert!(ring.windows(2).all(|pair| pair[0] <= pair[1]));
+        for idx in 0..3 {
+            let points = ring.iter().filter(|&&(_, shard)| shard == idx).count();
+            assert_eq!(points, VIRTUAL_NODES);
+        }
+    }
+
+    #[test]
+    fn ring_spreads_keys_over_every_shard() {
+        let sharded = sharded(&["a", "b", "c", "d"], true);
+        let routes = routes(&sharded);
+        for name in ["a", "b", "c", "d"] {
+            let share = routes.iter().filter(|route| *route == name).count();
+            assert!((1_500..3_500).contains(&share), "{name}: {share}");
+        }
+    }
+
+    #[test]
+    fn modulo_routing_without_a_ring() {
+        let sharded = sharded(&["0", "1", "2"], false);
+        for key in keys().take(100) {
+            let expected = (fnv1a(key.as_bytes()) % 3) as usize;
+            assert_eq!(sharded.shard_index(&key), expected);
+        }
+    }
+
+    #[test]
+    fn route_wraps_around_the_ring() {
+        let sharded = sharded(&["a", "b", "c"], true);
+        let layout = sharded.layout.borrow();
+        let ring = layout.ring.as_ref().unwrap();
+        let (first, last) = (ring[0], ring[ring.len() - 1]);
+
+        // Past the last point, a key belongs to the first point's shard.
+        let key = keys()
+            .find(|key| mix(fnv1a(key.as_bytes())) > last.0)
+            .expect("a key past the last point");
+        assert_eq!(sharded.route(&layout, key.as_bytes()), first.1);
+        let key = keys()
+            .find(|key| mix(fnv1a(key.as_bytes())) <= first.0)
+            .expect("a key before the first point");
+        assert_eq!(sharded.route(&layout, key.as_bytes()), first.1);
+    }
+
+    #[test]
+    fn changing_a_shard_moves_only_its_keys() {
+        let before = routes(&sharded(&["a", "b", "c"], true));
+
+        // Adding: keys either stay or move to the new shard.
+        let added = routes(&sharded(&["a", "b", "c", "d"], true));
+        let mut moved = 0;
+        for (before, after) in before.iter().zip(&added) {
+            if before != after {
+                assert_eq!(after, "d");
+                moved += 1;
+            }
+        }
+        assert!((1_500..3_500).contains(&moved), "{moved} keys moved");
+
+        // Removing: only the removed shard's keys move. Shard order does
+        // not matter, only names.
+        let removed = routes(&sharded(&["c", "a"], true));
+        for (before, after) in before.iter().zip(&removed) {
+            if before != "b" {
+                assert_eq!(before, after);
+            }
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    mod live {
+        use super::{ShardedConnector, fnv1a, keys};
+        use crate::DbConnector;
+        use std::path::PathBuf;
+
+        fn run<F: std::future::Future>(f: F) -> F::Output {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(f)
+        }
+
+        /// A SQLite database file per shard, removed on drop.
+        struct Files(Vec<PathBuf>);
+
+        impl Files {
+            fn connector(&mut self, name: &str) -> DbConnector {
+                let path = std::env::temp_dir().join(format!(
+                    "tin-sea-conn-shard-{}-{name}.db",
+                    std::process::id()
+                ));
+                let connector = DbConnector::new()
+                    .sqlite()
+                    .database(path.display().to_string());
+                self.0.push(path);
+                connector
+            }
+        }
+
+        impl Drop for Files {
+            fn drop(&mut self) {
+                for path in &self.0 {
+                    let _ = std::fs::remove_file(path);
+                }
+            }
+        }
+
+        #[test]
+        fn add_and_remove_shards() {
+            let mut files = Files(Vec::new());
+            run(async {
+                let sharded = ShardedConnector::connect_ring(
+                    [("a", files.connector("a")), ("b", files.connector("b"))],
+                    fnv1a,
+                )
+                .await
+                .unwrap();
+                let before: Vec<String> = keys().map(|key| sharded.shard_name(key)).collect();
+
+                sharded.add_shard("c", files.connector("c")).await.unwrap();
+                assert_eq!(sharded.shards().len(), 3);
+                let added: Vec<String> = keys().map(|key| sharded.shard_name(key)).collect();
+                for (before, after) in before.iter().zip(&added) {
+                    assert!(before == after || after == "c");
+                }
+
+                sharded.remove_shard("c").await.unwrap();
+                let removed: Vec<String> = keys().map(|key| sharded.shard_name(key)).collect();
+                assert_eq!(before, removed);
+
+                assert!(sharded.remove_shard("c").await.is_err());
+                sharded.remove_shard("b").await.unwrap();
+                // The last shard stays.
+                assert!(sharded.remove_shard("a").await.is_err());
+                sharded.close().await.unwrap();
+            });
+        }
+
+        #[test]
+        fn rejects_duplicate_shard_names() {
+            let mut files = Files(Vec::new());
+            run(async {
+                let duplicate = ShardedConnector::connect_ring(
+                    [
+                        ("a", files.connector("dup-a")),
+                        ("a", files.connector("dup-b")),
+                    ],
+                    fnv1a,
+                )
+                .await;
+                assert!(duplicate.is_err());
+                // Caught before connecting, so neither file was created.
+                assert!(files.0.iter().all(|path| !path.exists()));
+
+                let sharded = ShardedConnector::connect_ring([("a", files.connector("a2"))], fnv1a)
+                    .await
+                    .unwrap();
+                assert!(sharded.add_shard("a", files.connector("a3")).await.is_err());
+                assert_eq!(sharded.shards().len(), 1);
+                sharded.close().await.unwrap();
+            });
+        }
+
+        #[test]
+        fn modulo_shards_cannot_change() {
+            let mut files = Files(Vec::new());
+            run(async {
+                let sharded = ShardedConnector::connect([files.connector("m0")], fnv1a)
+                    .await
+                    .unwrap();
+                assert!(sharded.add_shard("1", files.connector("m1")).await.is_err());
+                assert!(sharded.remove_shard("0").await.is_err());
+                sharded.close().await.unwrap();
+            });
+        }
+    }
+}
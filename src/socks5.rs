@@ -0,0 +1,130 @@
+//! Outbound connections through a SOCKS5 proxy (RFC 1928, with RFC 1929
+//! username/password authentication).
+//!
+//! sqlx opens its own sockets, so the pool is pointed at a local
+//! [forwarder](crate::forward) that performs the SOCKS handshake for each
+//! connection and then relays bytes in both directions.
+
+use crate::{ConnectionError, Credentials};
+use std::net::IpAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Socks5Proxy {
+    pub(crate) addr: String,
+    pub(crate) credentials: Option<Credentials>,
+}
+
+fn io_error(context: &str) -> impl Fn(std::io::Error) -> ConnectionError + '_ {
+    move |e| ConnectionError::ConnectionFailed(format!("{context}: {e}"))
+}
+
+fn socks_error(message: String) -> ConnectionError {
+    ConnectionError::ConnectionFailed(format!("SOCKS5 proxy: {message}"))
+}
+
+impl Socks5Proxy {
+    /// Opens a connection to `target_host:target_port` through the proxy.
+    /// The host name is resolved by the proxy.
+    pub(crate) async fn connect(
+        &self,
+        target_host: &str,
+        target_port: u16,
+    ) -> Result<TcpStream, ConnectionError> {
+        let mut stream = TcpStream::connect(&self.addr)
+            .await
+            .map_err(io_error(&format!(
+                "Failed to reach SOCKS5 proxy {}",
+                self.addr
+            )))?;
+        let err = io_error("SOCKS5 handshake failed");
+
+        let methods: &[u8] = if self.credentials.is_some() {
+            &[0x00, 0x02]
+        } else {
+            &[0x00]
+        };
+        let mut greeting = vec![0x05, methods.len() as u8];
+        greeting.extend_from_slice(methods);
+        stream.write_all(&greeting).await.map_err(&err)?;
+
+        let mut reply = [0u8; 2];
+        stream.read_exact(&mut reply).await.map_err(&err)?;
+        match (reply[1], &self.credentials) {
+            (0x00, _) => {}
+            (0x02, Some(credentials)) => {
+                let (user, pass) = (
+                    credentials.username.as_bytes(),
+                    credentials.password.as_bytes(),
+                );
+                if user.len() > 255 || pass.len() > 255 {
+                    return Err(socks_error(
+                        "username and password are limited to 255 bytes".into(),
+                    ));
+                }
+                let mut auth = vec![0x01, user.len() as u8];
+                auth.extend_from_slice(user);
+                auth.push(pass.len() as u8);
+                auth.extend_from_slice(pass);
+                stream.write_all(&auth).await.map_err(&err)?;
+                stream.read_exact(&mut reply).await.map_err(&err)?;
+                if reply[1] != 0x00 {
+                    return Err(socks_error("authentication rejected".into()));
+                }
+            }
+            _ => return Err(socks_error("no acceptable authentication method".into())),
+        }
+
+        let mut request = vec![0x05, 0x01, 0x00];
+        match target_host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => {
+                request.push(0x01);
+                request.extend_from_slice(&ip.octets());
+            }
+            Ok(IpAddr::V6(ip)) => {
+                request.push(0x04);
+                request.extend_from_slice(&ip.octets());
+            }
+            Err(_) => {
+                if target_host.len() > 255 {
+                    return Err(socks_error(format!("host name too long: {target_host}")));
+                }
+                request.push(0x03);
+                request.push(target_host.len() as u8);
+                request.extend_from_slice(target_host.as_bytes());
+            }
+        }
+        request.extend_from_slice(&target_port.to_be_bytes());
+        stream.write_all(&request).await.map_err(&err)?;
+
+        let mut head = [0u8; 4];
+        stream.read_exact(&mut head).await.map_err(&err)?;
+        if head[1] != 0x00 {
+            let reason = match head[1] {
+                0x02 => "connection not allowed by ruleset",
+                0x03 => "network unreachable",
+                0x04 => "host unreachable",
+                0x05 => "connection refused",
+                0x06 => "TTL expired",
+                _ => "general failure",
+            };
+            return Err(socks_error(format!(
+                "CONNECT to {target_host}:{target_port} failed: {reason}"
+            )));
+        }
+        let addr_len = match head[3] {
+            0x01 => 4,
+            0x04 => 16,
+            0x03 => {
+                let mut len = [0u8; 1];
+                stream.read_exact(&mut len).await.map_err(&err)?;
+                len[0] as usize
+            }
+            other => return Err(socks_error(format!("unknown address type {other}"))),
+        };
+        let mut bound = vec![0u8; addr_len + 2];
+        stream.read_exact(&mut bound).await.map_err(&err)?;
+        Ok(stream)
+    }
+}
@@ -0,0 +1,114 @@
+//! Configuration profiles kept encrypted on disk with SOPS, decrypted in
+//! memory at load time.
+
+use crate::config::{DbConfig, parse_profiles};
+use crate::{ConnectionError, DbConnector, redact};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Where `sops` finds the key to decrypt with.
+#[derive(Clone, Default)]
+#[non_exhaustive]
+pub enum SopsKey {
+    /// Whatever `sops` picks up by itself: `SOPS_AGE_KEY_FILE`,
+    /// `SOPS_AGE_KEY`, `~/.config/sops/age/keys.txt`, or the cloud KMS,
+    /// PGP or Vault key named in the file's metadata.
+    #[default]
+    Environment,
+    /// An age identity file, as written by `age-keygen`.
+    AgeKeyFile(PathBuf),
+    /// An age secret key (`AGE-SECRET-KEY-1...`), e.g. injected by a CI
+    /// secret.
+    AgeKey(String),
+}
+
+impl fmt::Debug for SopsKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SopsKey::Environment => f.write_str("Environment"),
+            SopsKey::AgeKeyFile(path) => f.debug_tuple("AgeKeyFile").field(path).finish(),
+            SopsKey::AgeKey(_) => f.debug_tuple("AgeKey").field(&redact::MASK).finish(),
+        }
+    }
+}
+
+/// Runs `sops --decrypt` on `path` and returns the plaintext, which only
+/// ever passes through a pipe.
+fn decrypt(path: &Path, key: &SopsKey) -> Result<String, ConnectionError> {
+    let mut command = Command::new("sops");
+    command.arg("--decrypt").arg(path);
+    match key {
+        SopsKey::Environment => {}
+        SopsKey::AgeKeyFile(file) => {
+            command
+                .env("SOPS_AGE_KEY_FILE", file)
+                .env_remove("SOPS_AGE_KEY");
+        }
+        SopsKey::AgeKey(secret) => {
+            command
+                .env("SOPS_AGE_KEY", secret)
+                .env_remove("SOPS_AGE_KEY_FILE");
+        }
+    }
+    let output = command.output().map_err(|e| {
+        ConnectionError::InvalidConfig(format!(
+            "Failed to run sops to decrypt {}: {e}",
+            path.display()
+        ))
+    })?;
+    if !output.status.success() {
+        return Err(ConnectionError::InvalidConfig(format!(
+            "sops failed to decrypt {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    String::from_utf8(output.stdout).map_err(|_| {
+        ConnectionError::InvalidConfig(format!("Decrypted {} is not UTF-8", path.display()))
+    })
+}
+
+fn read_encrypted_profiles(
+    path: &Path,
+    key: &SopsKey,
+) -> Result<BTreeMap<String, DbConfig>, ConnectionError> {
+    parse_profiles(&decrypt(path, key)?, path)
+}
+
+impl DbConnector {
+    /// As [`from_profile`](Self::from_profile), for a TOML profile file
+    /// encrypted with SOPS (`sops --encrypt db.toml > db.enc.toml`).
+    ///
+    /// The file is decrypted by running `sops`, which must be on `PATH`,
+    /// with the key from `key`; the plaintext is only held in memory.
+    pub fn from_encrypted_profile<P: AsRef<Path>>(
+        path: P,
+        name: &str,
+        key: SopsKey,
+    ) -> Result<Self, ConnectionError> {
+        let path = path.as_ref();
+        read_encrypted_profiles(path, &key)?
+            .remove(name)
+            .ok_or_else(|| {
+                ConnectionError::InvalidConfig(format!(
+                    "Profile '{name}' not found in {}",
+                    path.display()
+                ))
+            })?
+            .try_into()
+    }
+
+    /// As [`load_profiles`](Self::load_profiles), for a TOML profile file
+    /// encrypted with SOPS.
+    pub fn load_encrypted_profiles<P: AsRef<Path>>(
+        path: P,
+        key: SopsKey,
+    ) -> Result<BTreeMap<String, DbConnector>, ConnectionError> {
+        read_encrypted_profiles(path.as_ref(), &key)?
+            .into_iter()
+            .map(|(name, config)| Ok((name, config.try_into()?)))
+            .collect()
+    }
+}
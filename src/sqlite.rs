@@ -0,0 +1,159 @@
+use crate::{ConnectionError, DatabaseType, DbConnector};
+use sea_orm::DatabaseConnection;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// SQLite connection split into a single-writer pool and a multi-reader pool.
+///
+/// SQLite allows one writer at a time; funnelling writes through a pool of
+/// size one avoids `SQLITE_BUSY` errors, while WAL mode lets readers proceed
+/// concurrently on their own read-only connections.
+#[derive(Debug, Clone)]
+pub struct SqliteHandle {
+    writer: DatabaseConnection,
+    reader: DatabaseConnection,
+}
+
+impl SqliteHandle {
+    /// Opens both pools from a SQLite connector. The reader pool uses the
+    /// connector's pool size; the writer pool always holds one connection.
+    pub async fn connect(connector: DbConnector) -> Result<Self, ConnectionError> {
+        if !matches!(connector.db_type(), Some(DatabaseType::SQLite)) {
+            return Err(ConnectionError::InvalidConfig(
+                "SqliteHandle requires a SQLite connector".into(),
+            ));
+        }
+
+        // The writer goes first so the file exists and is in WAL mode before
+        // read-only connections open it.
+        let mut writer_connector = connector.clone().max_connections(1).min_connections(1);
+        writer_connector.sqlite_wal = true;
+        let opt = writer_connector.connect_options()?;
+        let writer = writer_connector.open(opt).await?;
+
+        let mut reader_connector = connector;
+        reader_connector.sqlite_wal = true;
+        reader_connector.sqlite_read_only = true;
+        let opt = reader_connector.connect_options()?;
+        let reader = reader_connector.open(opt).await?;
+
+        Ok(Self { writer, reader })
+    }
+
+    /// Runs `f` against the single-writer pool.
+    pub async fn write<F, Fut, T>(&self, f: F) -> T
+    where
+        F: FnOnce(DatabaseConnection) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        f(self.writer.clone()).await
+    }
+
+    /// Runs `f` against the read-only pool.
+    pub async fn read<F, Fut, T>(&self, f: F) -> T
+    where
+        F: FnOnce(DatabaseConnection) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        f(self.reader.clone()).await
+    }
+
+    pub fn writer(&self) -> &DatabaseConnection {
+        &self.writer
+    }
+
+    pub fn reader(&self) -> &DatabaseConnection {
+        &self.reader
+    }
+
+    pub async fn close(self) -> Result<(), ConnectionError> {
+        self.reader
+            .close()
+            .await
+            .map_err(|e| ConnectionError::DatabaseError(e.to_string()))?;
+        self.writer
+            .close()
+            .await
+            .map_err(|e| ConnectionError::DatabaseError(e.to_string()))
+    }
+}
+
+/// A SQLite database in a uniquely named file under the system temp
+/// directory, deleted when dropped. Meant for tests, which can each open
+/// their own without clobbering one another when run in parallel.
+#[derive(Debug)]
+pub struct TempSqliteDb {
+    conn: DatabaseConnection,
+    path: PathBuf,
+}
+
+impl TempSqliteDb {
+    /// Creates and connects to a new temporary database with the default
+    /// settings.
+    pub async fn new() -> Result<Self, ConnectionError> {
+        Self::connect(DbConnector::new().sqlite()).await
+    }
+
+    /// As [`new`](Self::new), with the pool size, pragmas and other settings
+    /// of `connector`. Its database type and file are replaced.
+    pub async fn connect(connector: DbConnector) -> Result<Self, ConnectionError> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.subsec_nanos());
+        let path = std::env::temp_dir().join(format!(
+            "tin-sea-conn-{}-{nanos}-{}.sqlite",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let conn = connector
+            .sqlite()
+            .database(path.display().to_string())
+            .connect()
+            .await;
+        match conn {
+            Ok(conn) => Ok(Self { conn, path }),
+            Err(e) => {
+                remove_database_files(&path);
+                Err(e)
+            }
+        }
+    }
+
+    pub fn connection(&self) -> &DatabaseConnection {
+        &self.conn
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Closes the pool, then deletes the files. Dropping also deletes them,
+    /// but without waiting for the pool's connections to close, which some
+    /// platforms need before a file can be removed.
+    pub async fn close(self) -> Result<(), ConnectionError> {
+        self.conn
+            .clone()
+            .close()
+            .await
+            .map_err(|e| ConnectionError::DatabaseError(e.to_string()))
+    }
+}
+
+impl Drop for TempSqliteDb {
+    fn drop(&mut self) {
+        remove_database_files(&self.path);
+    }
+}
+
+/// Deletes a database file and the journal, WAL and shared-memory files
+/// SQLite keeps beside it.
+fn remove_database_files(path: &Path) {
+    for suffix in ["", "-journal", "-wal", "-shm"] {
+        let mut file = path.as_os_str().to_owned();
+        file.push(suffix);
+        let _ = std::fs::remove_file(file);
+    }
+}
@@ -0,0 +1,198 @@
+//! Local port forwarding through an SSH bastion, using the system OpenSSH
+//! client.
+//!
+//! Driving `ssh` rather than embedding an SSH implementation means the
+//! user's `known_hosts`, agent and `~/.ssh/config` apply exactly as they do
+//! on the command line.
+
+use crate::ConnectionError;
+use sea_orm::DatabaseConnection;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::process::{Child, Command};
+
+/// How often the supervisor checks whether the pool has been closed.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Bastion to forward through: `host` may carry a `:port`.
+#[derive(Debug, Clone)]
+pub(crate) struct SshTunnel {
+    pub(crate) host: String,
+    pub(crate) user: String,
+    pub(crate) key_path: PathBuf,
+}
+
+/// A running `ssh -L` process forwarding `127.0.0.1:local_port` to the
+/// database.
+pub(crate) struct OpenTunnel {
+    pub(crate) local_port: u16,
+    target: (String, u16),
+    child: Child,
+}
+
+impl SshTunnel {
+    fn command(&self, local_port: u16, target_host: &str, target_port: u16) -> Command {
+        let (host, port) = match self.host.rsplit_once(':') {
+            Some((host, port)) if !host.contains(':') => (host, Some(port)),
+            _ => (self.host.as_str(), None),
+        };
+        let target_host = if target_host.contains(':') {
+            format!("[{target_host}]")
+        } else {
+            target_host.to_string()
+        };
+
+        let mut cmd = Command::new("ssh");
+        cmd.arg("-N")
+            .args(["-o", "ExitOnForwardFailure=yes"])
+            .args(["-o", "BatchMode=yes"])
+            .args(["-o", "ServerAliveInterval=15"])
+            .args(["-o", "ServerAliveCountMax=3"])
+            .args(["-o", "LogLevel=ERROR"])
+            .arg("-i")
+            .arg(&self.key_path)
+            .arg("-L")
+            .arg(format!(
+                "127.0.0.1:{local_port}:{target_host}:{target_port}"
+            ));
+        if let Some(port) = port {
+            cmd.args(["-p", port]);
+        }
+        cmd.arg(format!("{}@{host}", self.user))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        cmd
+    }
+
+    async fn spawn(
+        &self,
+        local_port: u16,
+        target: &(String, u16),
+        timeout: Duration,
+    ) -> Result<Child, ConnectionError> {
+        let mut child = self
+            .command(local_port, &target.0, target.1)
+            .spawn()
+            .map_err(|e| ConnectionError::ConnectionFailed(format!("Failed to start ssh: {e}")))?;
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Ok(Some(status)) = child.try_wait() {
+                let mut stderr = String::new();
+                if let Some(mut pipe) = child.stderr.take() {
+                    let _ = pipe.read_to_string(&mut stderr).await;
+                }
+                return Err(ConnectionError::ConnectionFailed(format!(
+                    "SSH tunnel through {} exited with {status}: {}",
+                    self.host,
+                    stderr.trim()
+                )));
+            }
+            if TcpStream::connect(("127.0.0.1", local_port)).await.is_ok() {
+                return Ok(child);
+            }
+            if Instant::now() >= deadline {
+                return Err(ConnectionError::ConnectionFailed(format!(
+                    "SSH tunnel through {} was not ready within {}s",
+                    self.host,
+                    timeout.as_secs()
+                )));
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    /// Starts forwarding a free local port to `target_host:target_port` and
+    /// waits until it accepts connections.
+    pub(crate) async fn open(
+        &self,
+        target_host: &str,
+        target_port: u16,
+        timeout: Duration,
+    ) -> Result<OpenTunnel, ConnectionError> {
+        let local_port = TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .and_then(|listener| listener.local_addr())
+            .map_err(|e| {
+                ConnectionError::ConnectionFailed(format!("Failed to reserve a local port: {e}"))
+            })?
+            .port();
+        let target = (target_host.to_string(), target_port);
+        let child = self.spawn(local_port, &target, timeout).await?;
+        Ok(OpenTunnel {
+            local_port,
+            target,
+            child,
+        })
+    }
+}
+
+/// Keeps `tunnel` running for as long as the pool behind `conn` is open:
+/// `ssh` is restarted on the same local port if it exits, and stopped once
+/// the pool is closed.
+pub(crate) fn supervise(
+    conn: &DatabaseConnection,
+    config: SshTunnel,
+    tunnel: OpenTunnel,
+    timeout: Duration,
+) {
+    let conn = conn.clone();
+    crate::tasks::spawn("tin-sea-conn::ssh-tunnel", async move {
+        let OpenTunnel {
+            local_port,
+            target,
+            mut child,
+        } = tunnel;
+        loop {
+            let mut stderr = child.stderr.take().map(|pipe| BufReader::new(pipe).lines());
+            loop {
+                tokio::select! {
+                    status = child.wait() => {
+                        match status {
+                            Ok(status) => log::warn!("SSH tunnel through {} exited with {status}", config.host),
+                            Err(e) => log::warn!("SSH tunnel through {} failed: {e}", config.host),
+                        }
+                        break;
+                    }
+                    line = async {
+                        match &mut stderr {
+                            Some(lines) => lines.next_line().await,
+                            None => std::future::pending().await,
+                        }
+                    } => match line {
+                        Ok(Some(line)) => log::warn!("ssh: {line}"),
+                        _ => stderr = None,
+                    },
+                    _ = tokio::time::sleep(POLL_INTERVAL) => {
+                        if crate::managed::is_closed(&conn) {
+                            let _ = child.kill().await;
+                            return;
+                        }
+                    }
+                }
+            }
+
+            loop {
+                if crate::managed::is_closed(&conn) {
+                    return;
+                }
+                match config.spawn(local_port, &target, timeout).await {
+                    Ok(restarted) => {
+                        log::info!("Re-established SSH tunnel through {}", config.host);
+                        child = restarted;
+                        break;
+                    }
+                    Err(e) => {
+                        log::warn!("{e}");
+                        tokio::time::sleep(POLL_INTERVAL).await;
+                    }
+                }
+            }
+        }
+    });
+}
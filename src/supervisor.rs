@@ -0,0 +1,122 @@
+use crate::{ConnectionError, DbConnector};
+use sea_orm::DatabaseConnection;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// How often the current pool is pinged.
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// Consecutive failed pings after which the pool is considered dead.
+const FAILURES_BEFORE_REBUILD: u32 = 3;
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A connection that survives database restarts and failovers.
+///
+/// The pool is pinged every 5 seconds. After three failures in a row it is
+/// considered dead and rebuilt from the stored [`DbConnector`], retrying
+/// with exponential backoff (1s up to 60s) until the database is back; the
+/// new pool is then swapped in and the old one closed in the background.
+///
+/// Call [`connection()`](Self::connection) per unit of work rather than
+/// holding on to the returned handle, so work after a rebuild lands on the
+/// new pool. Supervision stops when every handle is dropped or the current
+/// pool is closed.
+///
+/// The connector's [`EventListener`](crate::EventListener)s hear when the
+/// pool is given up on, when a rebuilt one is swapped in, and when
+/// supervision notices the pool was closed.
+#[derive(Debug, Clone)]
+pub struct SupervisedConnection {
+    current: watch::Receiver<DatabaseConnection>,
+}
+
+impl SupervisedConnection {
+    /// Connects once, failing on the first error, and starts supervising.
+    pub async fn connect(connector: DbConnector) -> Result<Self, ConnectionError> {
+        let listeners = connector.listeners();
+        let (conn, mut host) = connector.clone().connect_any_host().await?;
+        listeners.notify(host.as_deref(), |l, e| l.on_connected(e));
+        let (tx, current) = watch::channel(conn);
+
+        crate::tasks::spawn("tin-sea-conn::connection-supervisor", async move {
+            let mut failures = 0;
+            loop {
+                tokio::time::sleep(CHECK_INTERVAL).await;
+                let conn = tx.borrow().clone();
+                if tx.is_closed() {
+                    return;
+                }
+                if crate::managed::is_closed(&conn) {
+                    listeners.notify(host.as_deref(), |l, e| l.on_pool_closed(e));
+                    return;
+                }
+                let error = match tokio::time::timeout(CHECK_INTERVAL, conn.ping()).await {
+                    Ok(Ok(())) => {
+                        failures = 0;
+                        continue;
+                    }
+                    Ok(Err(e)) => format!("Database ping failed: {e}"),
+                    Err(_) => "Database ping timed out".to_owned(),
+                };
+                log::warn!("{error}");
+                failures += 1;
+                if failures < FAILURES_BEFORE_REBUILD {
+                    continue;
+                }
+                listeners.notify(host.as_deref(), |l, e| l.on_connection_lost(e, &error));
+
+                let mut backoff = MIN_BACKOFF;
+                #[cfg(feature = "tracing")]
+                let mut attempt = 0u32;
+                let rebuilt = loop {
+                    if tx.is_closed() {
+                        return;
+                    }
+                    let fut = connector.clone().connect_any_host();
+                    #[cfg(feature = "tracing")]
+                    let fut = {
+                        attempt += 1;
+                        tracing::Instrument::instrument(
+                            fut,
+                            tracing::info_span!("reconnect", attempt),
+                        )
+                    };
+                    match fut.await {
+                        Ok((conn, connected_host)) => {
+                            host = connected_host;
+                            break conn;
+                        }
+                        Err(e) => {
+                            log::warn!(
+                                "Rebuilding the connection pool failed, retrying in {backoff:?}: {e}"
+                            );
+                            tokio::time::sleep(backoff).await;
+                            backoff = (backoff * 2).min(MAX_BACKOFF);
+                        }
+                    }
+                };
+                let old = tx.send_replace(rebuilt);
+                log::info!("Swapped in a rebuilt connection pool");
+                listeners.notify(host.as_deref(), |l, e| l.on_reconnected(e));
+                failures = 0;
+                // Connections checked out of a dead pool can take a while to
+                // fail, so don't hold up supervision on them.
+                tokio::spawn(async move {
+                    let _ = old.close().await;
+                });
+            }
+        });
+
+        Ok(Self { current })
+    }
+
+    /// The pool currently in service.
+    pub fn connection(&self) -> DatabaseConnection {
+        self.current.borrow().clone()
+    }
+
+    /// A receiver that is notified each time a rebuilt pool is swapped in.
+    pub fn subscribe(&self) -> watch::Receiver<DatabaseConnection> {
+        self.current.clone()
+    }
+}
@@ -0,0 +1,65 @@
+//! Registry of the background tasks spawned by this crate.
+//!
+//! Every task is spawned with a name so it can be told apart in
+//! tokio-console: build with the `tokio-console` feature and
+//! `RUSTFLAGS="--cfg tokio_unstable"` to attach the names to the runtime.
+
+use std::future::Future;
+use std::sync::Mutex;
+use tokio::task::AbortHandle;
+
+static TASKS: Mutex<Vec<BackgroundTask>> = Mutex::new(Vec::new());
+
+/// Handle to a background task spawned by this crate, such as a token
+/// refresher.
+#[derive(Debug, Clone)]
+pub struct BackgroundTask {
+    name: &'static str,
+    handle: AbortHandle,
+}
+
+impl BackgroundTask {
+    /// Name the task was spawned with, as shown in tokio-console.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Stop the task at its next await point.
+    pub fn abort(&self) {
+        self.handle.abort();
+    }
+}
+
+/// Lists the background tasks that are still running, e.g. to abort them
+/// during shutdown.
+pub fn tasks() -> Vec<BackgroundTask> {
+    let mut tasks = TASKS.lock().unwrap_or_else(|e| e.into_inner());
+    tasks.retain(|task| !task.is_finished());
+    tasks.clone()
+}
+
+/// Spawns `future` under `name` and records it in the registry.
+pub(crate) fn spawn<F>(name: &'static str, future: F) -> AbortHandle
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    #[cfg(all(tokio_unstable, feature = "tokio-console"))]
+    let handle = tokio::task::Builder::new()
+        .name(name)
+        .spawn(future)
+        .expect("failed to spawn background task");
+    #[cfg(not(all(tokio_unstable, feature = "tokio-console")))]
+    let handle = tokio::spawn(future);
+
+    let mut tasks = TASKS.lock().unwrap_or_else(|e| e.into_inner());
+    tasks.retain(|task| !task.is_finished());
+    tasks.push(BackgroundTask {
+        name,
+        handle: handle.abort_handle(),
+    });
+    handle.abort_handle()
+}
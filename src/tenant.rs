@@ -0,0 +1,161 @@
+use crate::{ConnectionError, DbConnector};
+use sea_orm::DatabaseConnection;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+/// Default limit on tenants with an open pool.
+const DEFAULT_MAX_TENANTS: usize = 64;
+
+type DatabaseFor = dyn Fn(&str) -> String + Send + Sync;
+
+struct Entry {
+    conn: Arc<OnceCell<DatabaseConnection>>,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Tenants {
+    entries: HashMap<String, Entry>,
+    clock: u64,
+}
+
+/// Per-tenant pools for database-per-tenant setups.
+///
+/// A pool is opened on a tenant's first [`get`](Self::get), from the base
+/// connector with the database returned by the mapping function, and cached.
+/// Once more than [`max_tenants`](Self::max_tenants) tenants have pools, the
+/// least recently used one is evicted and closed in the background after
+/// its in-flight work finishes, so call `get` per unit of work rather than
+/// holding on to a tenant's handle.
+///
+/// ```ignore
+/// let tenants = TenantConnectionManager::new(base, |tenant| format!("tenant_{tenant}"))
+///     .max_tenants(200);
+/// let db = tenants.get("acme").await?;
+/// ```
+///
+/// Tenant ids go into the database name (a file path for SQLite) as-is, so
+/// validate them before calling `get` if they come from requests.
+#[derive(Clone)]
+pub struct TenantConnectionManager {
+    base: DbConnector,
+    database_for: Arc<DatabaseFor>,
+    max_tenants: usize,
+    tenants: Arc<Mutex<Tenants>>,
+}
+
+impl fmt::Debug for TenantConnectionManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TenantConnectionManager")
+            .field("base", &self.base)
+            .field("max_tenants", &self.max_tenants)
+            .field("open_tenants", &self.open_tenants())
+            .finish()
+    }
+}
+
+impl TenantConnectionManager {
+    /// `database_for` maps a tenant id to the database to connect to.
+    pub fn new<F>(base: DbConnector, database_for: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        Self {
+            base,
+            database_for: Arc::new(database_for),
+            max_tenants: DEFAULT_MAX_TENANTS,
+            tenants: Arc::default(),
+        }
+    }
+
+    /// Maximum number of tenants with an open pool at once. Defaults to 64.
+    pub fn max_tenants(mut self, max: usize) -> Self {
+        self.max_tenants = max.max(1);
+        self
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Tenants> {
+        self.tenants.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The tenant's pool, opening it first if needed. Concurrent calls for
+    /// the same tenant share one connection attempt; a failed attempt is
+    /// retried on the next call.
+    pub async fn get(&self, tenant: &str) -> Result<DatabaseConnection, ConnectionError> {
+        let (cell, evicted) = {
+            let mut tenants = self.lock();
+            tenants.clock += 1;
+            let now = tenants.clock;
+            let mut evicted = None;
+            if !tenants.entries.contains_key(tenant) && tenants.entries.len() >= self.max_tenants {
+                let oldest = tenants
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(name, _)| name.clone());
+                evicted = oldest.and_then(|name| tenants.entries.remove(&name).map(|e| (name, e)));
+            }
+            let entry = tenants
+                .entries
+                .entry(tenant.to_string())
+                .or_insert_with(|| Entry {
+                    conn: Arc::default(),
+                    last_used: now,
+                });
+            entry.last_used = now;
+            (entry.conn.clone(), evicted)
+        };
+
+        if let Some((name, entry)) = evicted {
+            log::debug!("Evicting the pool of tenant '{name}'");
+            close_in_background(entry);
+        }
+
+        cell.get_or_try_init(|| async {
+            self.base
+                .clone()
+                .database((self.database_for)(tenant))
+                .connect()
+                .await
+                .map_err(|e| e.context(&format!("tenant '{tenant}'")))
+        })
+        .await
+        .cloned()
+    }
+
+    /// Closes and forgets the tenant's pool, e.g. after its database was
+    /// dropped or moved.
+    pub fn evict(&self, tenant: &str) {
+        if let Some(entry) = self.lock().entries.remove(tenant) {
+            close_in_background(entry);
+        }
+    }
+
+    /// Number of tenants with a cached pool.
+    pub fn open_tenants(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    /// Closes every tenant's pool, waiting for in-flight work.
+    pub async fn close(&self) -> Result<(), ConnectionError> {
+        let entries: Vec<_> = self.lock().entries.drain().collect();
+        for (name, entry) in entries {
+            if let Some(conn) = entry.conn.get() {
+                conn.close_by_ref()
+                    .await
+                    .map_err(|e| ConnectionError::DatabaseError(format!("tenant '{name}': {e}")))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn close_in_background(entry: Entry) {
+    if let Some(conn) = entry.conn.get().cloned() {
+        tokio::spawn(async move {
+            let _ = conn.close().await;
+        });
+    }
+}
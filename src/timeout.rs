@@ -0,0 +1,280 @@
+use crate::ConnectionError;
+use sea_orm::{
+    ConnectionTrait, DatabaseConnection, DatabaseTransaction, DbBackend, DbErr, ExecResult,
+    QueryResult, Statement, TransactionTrait,
+};
+use std::future::Future;
+use std::pin::Pin;
+#[cfg(feature = "mysql")]
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+/// Added to the client deadline for the server-side timeout, so the caller
+/// gets [`ConnectionError::QueryTimeout`] first and the server cancels the
+/// statement shortly after.
+const SERVER_TIMEOUT_MARGIN: Duration = Duration::from_millis(100);
+
+/// Bounds every query made through the returned handle by `timeout`.
+///
+/// ```ignore
+/// let db = with_timeout(&db, Duration::from_secs(2));
+/// let users = db.run(|conn| User::find().all(conn)).await?;
+/// ```
+pub fn with_timeout(conn: &DatabaseConnection, timeout: Duration) -> TimeoutConnection {
+    TimeoutConnection {
+        conn: conn.clone(),
+        timeout,
+        server_timeout: false,
+        #[cfg(feature = "mysql")]
+        mysql_variable: Arc::default(),
+    }
+}
+
+/// A connection whose queries fail with
+/// [`ConnectionError::QueryTimeout`] once their deadline passes, from
+/// [`with_timeout`].
+///
+/// The deadline is enforced client-side: the caller gets its error on time,
+/// but the server may keep running the statement until it notices the
+/// dropped request. With [`server_timeout`](Self::server_timeout),
+/// [`execute`](Self::execute), [`query_one`](Self::query_one),
+/// [`query_all`](Self::query_all) and [`transaction`](Self::transaction)
+/// have the server give up too, slightly after the client.
+#[derive(Debug, Clone)]
+pub struct TimeoutConnection {
+    conn: DatabaseConnection,
+    timeout: Duration,
+    server_timeout: bool,
+    /// The variable this MySQL-compatible server limits statements with,
+    /// once known.
+    #[cfg(feature = "mysql")]
+    mysql_variable: Arc<OnceLock<MySqlTimeoutVariable>>,
+}
+
+#[cfg(feature = "mysql")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MySqlTimeoutVariable {
+    /// MySQL: `max_execution_time`, in milliseconds.
+    MaxExecutionTime,
+    /// MariaDB: `max_statement_time`, in seconds.
+    MaxStatementTime,
+}
+
+#[cfg(feature = "mysql")]
+impl MySqlTimeoutVariable {
+    fn set(self, timeout: Duration) -> String {
+        match self {
+            Self::MaxExecutionTime => {
+                format!("SET SESSION max_execution_time = {}", timeout.as_millis())
+            }
+            Self::MaxStatementTime => {
+                format!("SET SESSION max_statement_time = {}", timeout.as_secs_f64())
+            }
+        }
+    }
+
+    fn reset(self) -> &'static str {
+        match self {
+            Self::MaxExecutionTime => "SET SESSION max_execution_time = DEFAULT",
+            Self::MaxStatementTime => "SET SESSION max_statement_time = DEFAULT",
+        }
+    }
+}
+
+type BoxFuture<'c, T> = Pin<Box<dyn Future<Output = Result<T, DbErr>> + Send + 'c>>;
+
+impl TimeoutConnection {
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Whether to set the server-side timeout as well (off by default):
+    /// `statement_timeout` on PostgreSQL, `max_execution_time` on MySQL
+    /// (which only limits `SELECT`s) or `max_statement_time` on MariaDB.
+    ///
+    /// The setting only lasts for a transaction, so each statement then
+    /// runs in one, which costs three extra round trips and rules out
+    /// statements such as `VACUUM` or `CREATE INDEX CONCURRENTLY`. To limit
+    /// every statement of the pool instead, set `statement_timeout` through
+    /// [`session_vars`](crate::DbConnector::session_vars).
+    pub fn server_timeout(mut self, enabled: bool) -> Self {
+        self.server_timeout = enabled;
+        self
+    }
+
+    /// The connection without a deadline.
+    pub fn connection(&self) -> &DatabaseConnection {
+        &self.conn
+    }
+
+    /// Runs `f`, which may make several queries, within the deadline.
+    pub async fn run<'a, F, Fut, T>(&'a self, f: F) -> Result<T, ConnectionError>
+    where
+        F: FnOnce(&'a DatabaseConnection) -> Fut,
+        Fut: Future<Output = Result<T, DbErr>> + 'a,
+    {
+        match tokio::time::timeout(self.timeout, f(&self.conn)).await {
+            Ok(result) => result.map_err(|e| ConnectionError::DatabaseError(e.to_string())),
+            Err(_) => Err(ConnectionError::QueryTimeout(self.timeout)),
+        }
+    }
+
+    /// Runs `f` in a transaction within the deadline, with the server-side
+    /// timeout set for the transaction's statements if
+    /// [`server_timeout`](Self::server_timeout) is on. The transaction is
+    /// committed if `f` succeeds and rolled back otherwise.
+    ///
+    /// ```ignore
+    /// let users = db
+    ///     .transaction(|txn| Box::pin(User::find().all(txn)))
+    ///     .await?;
+    /// ```
+    pub async fn transaction<F, T>(&self, f: F) -> Result<T, ConnectionError>
+    where
+        F: for<'c> FnOnce(&'c DatabaseTransaction) -> BoxFuture<'c, T>,
+        T: Send,
+    {
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        let timed_out = || ConnectionError::QueryTimeout(self.timeout);
+        let failed = |e: DbErr| ConnectionError::DatabaseError(e.to_string());
+
+        let (txn, reset) = tokio::time::timeout_at(deadline, self.begin())
+            .await
+            .map_err(|_| timed_out())?
+            .map_err(failed)?;
+
+        let result = tokio::time::timeout_at(deadline, f(&txn)).await;
+        let cleanup = async move {
+            if let Some(reset) = reset {
+                txn.execute_unprepared(reset).await?;
+            }
+            Ok::<_, DbErr>(txn)
+        };
+        match result {
+            Ok(Ok(value)) => {
+                let txn = cleanup.await.map_err(failed)?;
+                txn.commit().await.map_err(failed)?;
+                Ok(value)
+            }
+            Ok(Err(e)) => {
+                if let Ok(txn) = cleanup.await {
+                    let _ = txn.rollback().await;
+                }
+                Err(failed(e))
+            }
+            Err(_) => {
+                // The statement is still running until the server-side
+                // timeout cancels it; clean up once it has.
+                tokio::spawn(async move {
+                    match cleanup.await {
+                        Ok(txn) => {
+                            let _ = txn.rollback().await;
+                        }
+                        Err(e) => log::warn!(
+                            "Resetting the server-side timeout after a query timeout failed: {e}"
+                        ),
+                    }
+                });
+                Err(timed_out())
+            }
+        }
+    }
+
+    /// Begins a transaction and sets the server-side timeout in it if asked
+    /// to, returning the statement that resets the timeout when one is needed
+    /// before the connection goes back to the pool.
+    async fn begin(&self) -> Result<(DatabaseTransaction, Option<&'static str>), DbErr> {
+        let txn = self.conn.begin().await?;
+        if !self.server_timeout {
+            return Ok((txn, None));
+        }
+        #[cfg_attr(
+            not(any(feature = "postgres", feature = "mysql")),
+            allow(unused_variables)
+        )]
+        let server_timeout = self.timeout + SERVER_TIMEOUT_MARGIN;
+        match self.conn.get_database_backend() {
+            #[cfg(feature = "postgres")]
+            DbBackend::Postgres => {
+                // SET LOCAL ends with the transaction.
+                txn.execute_unprepared(&format!(
+                    "SET LOCAL statement_timeout = {}",
+                    server_timeout.as_millis()
+                ))
+                .await?;
+                Ok((txn, None))
+            }
+            #[cfg(feature = "mysql")]
+            DbBackend::MySql => {
+                let variable = match self.mysql_variable.get() {
+                    Some(&variable) => {
+                        txn.execute_unprepared(&variable.set(server_timeout))
+                            .await?;
+                        variable
+                    }
+                    None => {
+                        let mysql = MySqlTimeoutVariable::MaxExecutionTime;
+                        let variable =
+                            match txn.execute_unprepared(&mysql.set(server_timeout)).await {
+                                Ok(_) => mysql,
+                                // MariaDB has no max_execution_time.
+                                Err(_) => {
+                                    let mariadb = MySqlTimeoutVariable::MaxStatementTime;
+                                    txn.execute_unprepared(&mariadb.set(server_timeout)).await?;
+                                    mariadb
+                                }
+                            };
+                        let _ = self.mysql_variable.set(variable);
+                        variable
+                    }
+                };
+                Ok((txn, Some(variable.reset())))
+            }
+            _ => Ok((txn, None)),
+        }
+    }
+
+    /// Whether single statements go through
+    /// [`transaction`](Self::transaction) to set the server-side timeout.
+    fn syncs_server_timeout(&self) -> bool {
+        self.server_timeout
+            && matches!(
+                self.conn.get_database_backend(),
+                DbBackend::Postgres | DbBackend::MySql
+            )
+    }
+
+    pub async fn execute(&self, stmt: Statement) -> Result<ExecResult, ConnectionError> {
+        if self.syncs_server_timeout() {
+            self.transaction(|txn| Box::pin(txn.execute(stmt))).await
+        } else {
+            self.run(|conn| conn.execute(stmt)).await
+        }
+    }
+
+    pub async fn execute_unprepared(&self, sql: &str) -> Result<ExecResult, ConnectionError> {
+        if self.syncs_server_timeout() {
+            let sql = sql.to_string();
+            self.transaction(|txn| Box::pin(async move { txn.execute_unprepared(&sql).await }))
+                .await
+        } else {
+            self.run(|conn| conn.execute_unprepared(sql)).await
+        }
+    }
+
+    pub async fn query_one(&self, stmt: Statement) -> Result<Option<QueryResult>, ConnectionError> {
+        if self.syncs_server_timeout() {
+            self.transaction(|txn| Box::pin(txn.query_one(stmt))).await
+        } else {
+            self.run(|conn| conn.query_one(stmt)).await
+        }
+    }
+
+    pub async fn query_all(&self, stmt: Statement) -> Result<Vec<QueryResult>, ConnectionError> {
+        if self.syncs_server_timeout() {
+            self.transaction(|txn| Box::pin(txn.query_all(stmt))).await
+        } else {
+            self.run(|conn| conn.query_all(stmt)).await
+        }
+    }
+}
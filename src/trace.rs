@@ -0,0 +1,18 @@
+//! Spans for the `tracing` feature.
+
+use crate::ConnectionError;
+use std::future::Future;
+use tracing::Instrument;
+
+/// Runs `fut` in `span` and records the error code on the span if it fails.
+/// The span must declare an empty `error.code` field.
+pub(crate) async fn instrument<T>(
+    span: tracing::Span,
+    fut: impl Future<Output = Result<T, ConnectionError>>,
+) -> Result<T, ConnectionError> {
+    let result = fut.instrument(span.clone()).await;
+    if let Err(e) = &result {
+        span.record("error.code", e.code().as_str());
+    }
+    result
+}
@@ -0,0 +1,211 @@
+//! Dynamic database credentials from HashiCorp Vault's database secrets
+//! engine.
+
+use crate::ConnectionError;
+use sea_orm::DatabaseConnection;
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const DEFAULT_ADDR: &str = "https://127.0.0.1:8200";
+
+/// How long to wait before retrying after Vault could not be reached.
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A role in a database secrets engine mount, e.g. `database` / `app-rw`.
+#[derive(Debug, Clone)]
+pub(crate) struct VaultRole {
+    pub(crate) mount: String,
+    pub(crate) role: String,
+}
+
+/// A lease on a set of dynamic credentials.
+#[derive(Debug, Clone)]
+pub(crate) struct Lease {
+    id: String,
+    duration: Duration,
+    renewable: bool,
+}
+
+pub(crate) struct Credentials {
+    pub(crate) username: String,
+    pub(crate) password: String,
+}
+
+#[derive(Deserialize)]
+struct LeaseResponse {
+    lease_id: String,
+    lease_duration: u64,
+    renewable: bool,
+    data: Option<CredentialsData>,
+}
+
+#[derive(Deserialize)]
+struct CredentialsData {
+    username: String,
+    password: String,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    errors: Vec<String>,
+}
+
+impl From<&LeaseResponse> for Lease {
+    fn from(response: &LeaseResponse) -> Self {
+        Lease {
+            id: response.lease_id.clone(),
+            duration: Duration::from_secs(response.lease_duration),
+            renewable: response.renewable,
+        }
+    }
+}
+
+/// Client settings from the standard `VAULT_*` environment variables, the
+/// same ones the `vault` CLI reads.
+struct Client {
+    addr: String,
+    token: String,
+    namespace: Option<String>,
+    ca_cert: Option<PathBuf>,
+}
+
+impl Client {
+    fn from_env() -> Result<Self, ConnectionError> {
+        let token = match std::env::var("VAULT_TOKEN") {
+            Ok(token) => token,
+            Err(_) => {
+                let home = std::env::var_os("HOME").ok_or_else(|| {
+                    ConnectionError::InvalidConfig("VAULT_TOKEN is not set".into())
+                })?;
+                crate::env::read_secret_file(&PathBuf::from(home).join(".vault-token"))?
+            }
+        };
+        Ok(Client {
+            addr: std::env::var("VAULT_ADDR")
+                .unwrap_or_else(|_| DEFAULT_ADDR.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            token,
+            namespace: std::env::var("VAULT_NAMESPACE").ok(),
+            ca_cert: std::env::var_os("VAULT_CACERT").map(PathBuf::from),
+        })
+    }
+
+    async fn send(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&str>,
+    ) -> Result<LeaseResponse, ConnectionError> {
+        let mut headers = vec![("X-Vault-Token", self.token.as_str())];
+        if let Some(namespace) = &self.namespace {
+            headers.push(("X-Vault-Namespace", namespace));
+        }
+        if body.is_some() {
+            headers.push(("Content-Type", "application/json"));
+        }
+        let url = format!("{}/v1/{path}", self.addr);
+        let (status, body) =
+            crate::http::request(method, &url, &headers, body, self.ca_cert.as_deref()).await?;
+        if status != 200 {
+            let detail = serde_json::from_str::<ErrorResponse>(&body)
+                .map(|e| e.errors.join("; "))
+                .unwrap_or_default();
+            return Err(ConnectionError::ConnectionFailed(format!(
+                "Vault returned HTTP {status} for {path}: {detail}"
+            )));
+        }
+        serde_json::from_str(&body).map_err(|e| {
+            ConnectionError::ConnectionFailed(format!("Invalid Vault response for {path}: {e}"))
+        })
+    }
+}
+
+/// Reads a fresh set of credentials for `role`.
+pub(crate) async fn credentials(role: &VaultRole) -> Result<(Credentials, Lease), ConnectionError> {
+    let client = Client::from_env()?;
+    let path = format!(
+        "{}/creds/{}",
+        role.mount.trim_matches('/'),
+        role.role.trim_matches('/')
+    );
+    let response = client.send("GET", &path, None).await?;
+    let lease = Lease::from(&response);
+    let data = response.data.ok_or_else(|| {
+        ConnectionError::ConnectionFailed(format!("Vault returned no credentials for {path}"))
+    })?;
+    Ok((
+        Credentials {
+            username: data.username,
+            password: data.password,
+        },
+        lease,
+    ))
+}
+
+async fn renew(lease: &Lease) -> Result<Lease, ConnectionError> {
+    let client = Client::from_env()?;
+    let body = serde_json::json!({
+        "lease_id": lease.id,
+        "increment": lease.duration.as_secs(),
+    })
+    .to_string();
+    let response = client.send("PUT", "sys/leases/renew", Some(&body)).await?;
+    Ok(Lease::from(&response))
+}
+
+/// Keeps the credentials behind `conn` valid: renews `lease` at two thirds
+/// of its duration and, once Vault stops extending it (the role's max TTL is
+/// near), reads new credentials and installs them in the pool. Connections
+/// opened with the old credentials keep working until their lease expires
+/// and are replaced as they are retired by the idle timeout.
+pub(crate) fn spawn_lease_manager(conn: &DatabaseConnection, role: VaultRole, lease: Lease) {
+    let conn = conn.clone();
+    crate::tasks::spawn("tin-sea-conn::vault-lease", async move {
+        let mut lease = lease;
+        let mut ttl = lease.duration;
+        let mut wait = lease.duration * 2 / 3;
+        loop {
+            tokio::time::sleep(wait).await;
+            if crate::managed::is_closed(&conn) {
+                break;
+            }
+
+            if lease.renewable {
+                match renew(&lease).await {
+                    // A lease that comes back much shorter than requested is
+                    // running into the max TTL; rotate instead.
+                    Ok(renewed) if renewed.duration * 3 >= ttl => {
+                        wait = renewed.duration * 2 / 3;
+                        lease = renewed;
+                        continue;
+                    }
+                    Ok(_) => {}
+                    Err(e) => log::warn!("Failed to renew Vault lease {}: {e}", lease.id),
+                }
+            }
+
+            match credentials(&role).await {
+                Ok((credentials, new_lease)) => {
+                    crate::refresh::set_credentials(
+                        &conn,
+                        Some(&credentials.username),
+                        &credentials.password,
+                    );
+                    log::info!(
+                        "Rotated database credentials from Vault lease {}",
+                        new_lease.id
+                    );
+                    ttl = new_lease.duration;
+                    wait = new_lease.duration * 2 / 3;
+                    lease = new_lease;
+                }
+                Err(e) => {
+                    log::warn!("Failed to read database credentials from Vault: {e}");
+                    wait = RETRY_INTERVAL;
+                }
+            }
+        }
+    });
+}
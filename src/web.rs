@@ -0,0 +1,59 @@
+//! The request extractor shared by the `axum` and `actix` features.
+
+use crate::{AcquiredConn, ConnectionError};
+use sea_orm::DatabaseConnection;
+use std::fmt;
+use std::ops::Deref;
+
+/// A connection from the application's [`TinSeaConn`](crate::TinSeaConn),
+/// extracted in axum or actix-web handlers and held for the rest of the
+/// request.
+///
+/// The guard is an [`AcquiredConn`], so
+/// [`close_gracefully`](crate::TinSeaConn::close_gracefully) waits for
+/// requests still using it. Once the pool is shutting down, the request is
+/// rejected with `503 Service Unavailable`.
+///
+/// ```ignore
+/// async fn list_users(Db(conn): Db) -> Result<Json<Vec<user::Model>>, AppError> {
+///     Ok(Json(User::find().all(&*conn).await?))
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Db(pub AcquiredConn);
+
+impl Deref for Db {
+    type Target = DatabaseConnection;
+
+    fn deref(&self) -> &DatabaseConnection {
+        &self.0
+    }
+}
+
+/// Why [`Db`] could not be extracted. Responds with
+/// [`status_code()`](Self::status_code) and the error as plain text.
+#[derive(Debug)]
+pub struct DbRejection(pub ConnectionError);
+
+impl DbRejection {
+    /// 500 when the application has not registered a pool, 503 when the
+    /// pool is shutting down or closed.
+    pub fn status_code(&self) -> u16 {
+        match self.0 {
+            ConnectionError::InvalidConfig(_) => 500,
+            _ => 503,
+        }
+    }
+}
+
+impl fmt::Display for DbRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for DbRejection {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}